@@ -0,0 +1,39 @@
+use std::time::Instant;
+use aoc_2019_rust::intcode::Automaton;
+use aoc_2019_rust::intcode::memory::{DenseMemory, SparseMemory};
+use aoc_2019_rust::intcode::word::Word;
+use aoc_2019_rust::read_input;
+
+// Runs the day9 BOOST program in sensor boost mode on both memory backends,
+// and on i64 words.
+// Run with `cargo bench --bench memory`.
+fn main() {
+    let program = read_input("inputs/day9.txt");
+
+    let sparse = bench("SparseMemory", || {
+        Automaton::new_with_program(&program).with_memory(SparseMemory::new()).add_initial_input(2)
+    });
+    let dense = bench("DenseMemory", || {
+        Automaton::new_with_program(&program).with_memory(DenseMemory::new()).add_initial_input(2)
+    });
+    let words = bench("DenseMemory<i64>", || {
+        Automaton::<i64>::new_with_words(&program).add_initial_input(2)
+    });
+
+    println!("speedup: {:.2}x", sparse as f64 / dense as f64);
+    println!("i64 speedup: {:.2}x", dense as f64 / words as f64);
+}
+
+fn bench<W: Word>(name: &str, build: impl Fn() -> Automaton<W>) -> u128 {
+    const ITERATIONS: u32 = 5;
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let mut automaton = build();
+        automaton.run().expect("Intcode program failed");
+        assert_eq!(automaton.get_output(1).map(|out| out.len()), Some(1));
+    }
+    let micros = start.elapsed().as_micros() / ITERATIONS as u128;
+    println!("{name}: {micros} micro secs per run");
+    micros
+}
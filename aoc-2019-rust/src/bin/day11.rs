@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::time::Instant;
 use aoc_2019_rust::intcode::Automaton;
+use aoc_2019_rust::intcode::controller::{drive, Controller};
 use aoc_2019_rust::read_input;
 
 fn main() {
@@ -21,16 +22,17 @@ fn main() {
 
 fn part1() {
     let program = read_input("inputs/day11.txt");
-    let mut robot = Robot::new(&program);
-    robot.run();
+    let mut robot = Robot::new();
+    drive(&mut Automaton::new_with_program(&program), &mut robot).expect("Intcode program failed");
     let painted_panels = robot.number_of_painted_panels();
     println!("{painted_panels}");
 }
 
 fn part2() {
     let program = read_input("inputs/day11.txt");
-    let mut robot = Robot::with_input(&program, Color::WHITE);
-    robot.run();
+    let mut computer = Automaton::new_with_program(&program).add_initial_input(Color::WHITE.into());
+    let mut robot = Robot::new();
+    drive(&mut computer, &mut robot).expect("Intcode program failed");
     robot.print_panels();
 }
 
@@ -69,7 +71,6 @@ type Coordinate = (i32,i32);
 struct Robot {
     location: (i32, i32),
     direction: Direction,
-    computer: Automaton,
     panels: HashMap<Coordinate,Color>,
     min_x: i32,
     max_x: i32,
@@ -78,11 +79,10 @@ struct Robot {
 }
 
 impl Robot {
-    fn new(program: &str) -> Self {
+    fn new() -> Self {
         Self {
             location: (0, 0),
             direction: Direction::UP,
-            computer: Automaton::new_with_program(program),
             panels: Default::default(),
             min_x: 0,
             max_x: 0,
@@ -91,39 +91,6 @@ impl Robot {
         }
     }
 
-    fn with_input(program: &str, initial_panel_color: Color) -> Self {
-        Self {
-            location: (0, 0),
-            direction: Direction::UP,
-            computer: Automaton::new_with_program(program).add_initial_input(initial_panel_color.into()),
-            panels: Default::default(),
-            min_x: 0,
-            max_x: 0,
-            min_y: 0,
-            max_y: 0,
-        }
-    }
-
-    fn run(&mut self) {
-        while !self.computer.halted {
-            self.computer.run();
-            if !self.computer.output.is_empty() {
-                let (color_to_paint, direction_to_turn) = self.computer.get_last_2_outputs();
-
-                let color_to_paint = Color::from(color_to_paint);
-                self.paint_panel(color_to_paint);
-
-                match direction_to_turn {
-                    0 => self.turn_left(),
-                    1 | _ => self.turn_right(),
-                }
-
-                self.move_forward();
-            }
-            self.computer.runtime_input(self.current_panel_color());
-        }
-    }
-
     fn turn_left(&mut self) {
         self.direction = match self.direction {
             Direction::UP => Direction::LEFT,
@@ -185,3 +152,38 @@ impl Robot {
         }
     }
 }
+
+impl Controller for Robot {
+    type Frame = (Color, i128);
+
+    fn on_output(&mut self, (color_to_paint, turn): (Color, i128)) {
+        self.paint_panel(color_to_paint);
+
+        match turn {
+            0 => self.turn_left(),
+            _ => self.turn_right(),
+        }
+
+        self.move_forward();
+    }
+
+    fn next_input(&mut self) -> Option<i128> {
+        Some(self.current_panel_color())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aoc_2019_rust::intcode::controller::Controller;
+    use crate::Robot;
+
+    #[test]
+    fn test_example() {
+        let mut robot = Robot::new();
+        for (color, turn) in [(1, 0), (0, 0), (1, 0), (1, 0), (0, 1), (1, 0), (1, 0)] {
+            robot.on_output((color.into(), turn));
+        }
+        assert_eq!(robot.number_of_painted_panels(), 6);
+        assert_eq!(robot.next_input(), Some(0));
+    }
+}
@@ -1,6 +1,7 @@
 use std::cmp::Ordering;
 use std::time::Instant;
 use aoc_2019_rust::intcode::Automaton;
+use aoc_2019_rust::intcode::controller::{drive, Controller};
 use aoc_2019_rust::read_input;
 
 fn main() {
@@ -22,73 +23,42 @@ fn main() {
 fn part1() {
     let program = read_input("inputs/day13.txt");
     let mut automaton = Automaton::new_with_program(&program);
-    automaton.run();
-    let block_tiles = automaton.output.iter()
-        .enumerate()
-        .filter(| &(index,_) | (index + 1) % 3 == 0 )
-        .map(| (_,&value) | Tile::from(value))
-        .filter(| tile | tile == &Tile::Block)
+    automaton.run().expect("Intcode program failed");
+    let block_tiles = automaton.read_frames::<(i128, i128, Tile)>().expect("Incomplete screen output").iter()
+        .filter(| (_, _, tile) | tile == &Tile::Block)
         .count();
     println!("{block_tiles}");
 }
 
 fn part2() {
-    let input = read_input("inputs/day13.txt");
-    let program = {
-        let mut program = String::from("2");
-        program.push_str(&input[1..]);
-        program
-    };
-    let mut arcade = Arcade::new(&program);
-    arcade.play();
+    let program = read_input("inputs/day13.txt");
+    // address 0 holds the number of quarters, 2 plays for free
+    let mut automaton = Automaton::new_with_program(&program).with_patch(0, 2);
+    let mut arcade = Arcade::new();
+    drive(&mut automaton, &mut arcade).expect("Intcode program failed");
     println!("{}", arcade.score);
 }
 
 struct Arcade {
-    computer: Automaton,
     ball_position: (i128,i128),
     paddle_position: (i128,i128),
     score: i128,
 }
 
 impl Arcade {
-    fn new(program : &str) -> Self {
+    fn new() -> Self {
         Self {
-            computer: Automaton::new_with_program(program),
             ball_position: (0, 0),
             paddle_position: (0, 0),
             score: 0,
         }
     }
+}
 
-    fn play(&mut self) {
-        while !self.computer.halted {
-            self.computer.run();
-            // 1 process all unvisited outputs
-            // println!("{}", self.computer.output.len());
-            while self.computer.has_output() {
-                // println!("getting 3 outputs");
-                let output = self.computer.get_output(3);
-                // println!("{:?}", output);
-                if let Some(output) = output {
-                    self.process_output(&output);
-                }
-            }
-
-            // 2 determine input
-            match self.paddle_position.0.cmp(&self.ball_position.0) {
-                Ordering::Less => { self.computer.runtime_input(1) }
-                Ordering::Equal => { self.computer.runtime_input(0) }
-                Ordering::Greater => { self.computer.runtime_input(-1) }
-            }
-        }
-    }
-
-    fn process_output(&mut self, output: &Vec<i128>) {
-        let x = *output.get(0).unwrap();
-        let y = *output.get(1).unwrap();
-        let value = *output.get(2).unwrap();
+impl Controller for Arcade {
+    type Frame = (i128, i128, i128);
 
+    fn on_output(&mut self, (x, y, value): (i128, i128, i128)) {
         if x == -1 && y == 0 {
             self.score = value;
         } else {
@@ -101,6 +71,15 @@ impl Arcade {
             }
         }
     }
+
+    fn next_input(&mut self) -> Option<i128> {
+        let joystick = match self.paddle_position.0.cmp(&self.ball_position.0) {
+            Ordering::Less => { 1 }
+            Ordering::Equal => { 0 }
+            Ordering::Greater => { -1 }
+        };
+        Some(joystick)
+    }
 }
 
 #[derive(PartialEq)]
@@ -123,3 +102,22 @@ impl From<i128> for Tile {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use aoc_2019_rust::intcode::controller::Controller;
+    use crate::Arcade;
+
+    #[test]
+    fn test_joystick_follows_ball() {
+        let mut arcade = Arcade::new();
+        arcade.on_output((5, 20, 3));
+        arcade.on_output((2, 10, 4));
+        arcade.on_output((-1, 0, 1234));
+        assert_eq!(arcade.next_input(), Some(-1));
+        assert_eq!(arcade.score, 1234);
+
+        arcade.on_output((5, 11, 4));
+        assert_eq!(arcade.next_input(), Some(0));
+    }
+}
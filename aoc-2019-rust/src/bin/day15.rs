@@ -1,5 +1,7 @@
+use std::collections::{HashSet, VecDeque};
 use std::time::Instant;
 use aoc_2019_rust::intcode::Automaton;
+use aoc_2019_rust::intcode::controller::{drive, Controller};
 use aoc_2019_rust::read_input;
 
 fn main() {
@@ -21,12 +23,21 @@ fn main() {
 fn part1() {
     let input = read_input("inputs/day15.txt");
 
-    let mut remote = RemoteControl::new(&input);
-    remote.start();
+    let remote = RemoteControl::new(&input);
+    let oxygen_system = remote.find_oxygen_system().expect("Expected to find the oxygen system");
+    println!("{}", oxygen_system.distance);
 }
 
 fn part2() {
-    // let input = read_input("inputs/day15.txt");
+    let input = read_input("inputs/day15.txt");
+
+    let remote = RemoteControl::new(&input);
+    let oxygen_system = remote.find_oxygen_system().expect("Expected to find the oxygen system");
+    let minutes = RemoteControl::from_computer(oxygen_system.computer).explore().iter()
+        .map(|location| location.distance)
+        .max()
+        .unwrap_or(0);
+    println!("{minutes}");
 }
 
 #[derive(Debug)]
@@ -48,7 +59,7 @@ impl From<&Command> for i128 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 enum StatusCode {
     HitWall,
     Moved,
@@ -81,57 +92,90 @@ const COMMANDS: [Command; 4] = [Command::North, Command::East, Command::South, C
 
 struct RemoteControl {
     computer: Automaton,
-    current: Coordinate,
-    visited: Vec<Coordinate>,
 }
 
-impl RemoteControl {
-    fn new(program: &str) -> Self {
+/// Sends one command to the droid and waits for the status it reports.
+struct Move {
+    command: Option<i128>,
+    status: Option<StatusCode>,
+}
+
+impl Move {
+    fn new(command: &Command) -> Self {
         Self {
-            computer: Automaton::new_with_program(program),
-            current: (0, 0),
-            visited: vec![],
+            command: Some(command.into()),
+            status: None,
         }
     }
+}
+
+impl Controller for Move {
+    type Frame = i128;
+
+    fn on_output(&mut self, status: i128) {
+        self.status = Some(StatusCode::from(status));
+    }
 
-    fn start(&mut self) {
-        self.computer.run();
+    fn next_input(&mut self) -> Option<i128> {
+        self.command.take()
+    }
 
-        let minimal = COMMANDS.iter()
-            .filter_map(|cmd| self.step(cmd, (0,0), &mut vec![]))
-            .min()
-            .unwrap_or(0);
-        println!("{minimal}");
+    fn is_done(&self) -> bool {
+        self.status.is_some()
     }
+}
 
-    fn step(&mut self, command: &Command, current_coordinate : Coordinate, visited: &mut Vec<Coordinate>) -> Option<usize> {
-        let new_coordinate = new_coordinate(command, &current_coordinate);
-        println!("from {:?} go {:?} to {:?}", current_coordinate, command, new_coordinate);
-        if visited.contains(&new_coordinate) {
-            println!(" we were already at {:?}", new_coordinate);
-            return None;
+struct Location {
+    distance: usize,
+    status: StatusCode,
+    computer: Automaton,
+}
+
+impl RemoteControl {
+    fn new(program: &str) -> Self {
+        Self::from_computer(Automaton::new_with_program(program))
+    }
+
+    fn from_computer(computer: Automaton) -> Self {
+        Self {
+            computer,
         }
+    }
 
-        self.computer.runtime_input(command.into());
-        self.computer.run();
-        let code = StatusCode::from(self.computer.get_last_output());
-        println!(" which yields {code:?}");
-        match code {
-            StatusCode::HitWall => { None }
-            StatusCode::Moved => {
-                visited.push(new_coordinate);
-                let next_step = COMMANDS.iter()
-                    .filter_map(|cmd| self.step(cmd, new_coordinate, visited))
-                    .min();
-                if let Some(next_step) = next_step {
-                    Some(1 + next_step)
-                } else { None }
-            } // continue recursion
-            StatusCode::Finished => {
-                println!("found at {:?}", new_coordinate);
-                Some(1)
+    fn find_oxygen_system(&self) -> Option<Location> {
+        self.explore().into_iter()
+            .find(|location| location.status == StatusCode::Finished)
+    }
+
+    /// Breadth-first search over every position the droid can reach, in order
+    /// of distance. Each frontier position carries its own fork of the droid's
+    /// computer, so moves never have to be walked back.
+    fn explore(&self) -> Vec<Location> {
+        let start = (0, 0);
+        let mut visited = HashSet::from([start]);
+        let mut frontier = VecDeque::from([(start, 0, self.computer.fork())]);
+        let mut locations = vec![];
+
+        while let Some((current, distance, computer)) = frontier.pop_front() {
+            for command in COMMANDS.iter() {
+                let coordinate = new_coordinate(command, &current);
+                if !visited.insert(coordinate) {
+                    continue;
+                }
+
+                let mut computer = computer.fork();
+                let mut step = Move::new(command);
+                drive(&mut computer, &mut step).expect("Intcode program failed");
+                let status = step.status.expect("Droid stopped without reporting a status");
+                if status == StatusCode::HitWall {
+                    continue;
+                }
+
+                frontier.push_back((coordinate, distance + 1, computer.fork()));
+                locations.push(Location { distance: distance + 1, status, computer });
             }
         }
+        locations
     }
 }
 
@@ -0,0 +1,144 @@
+use std::time::Instant;
+use aoc_2019_rust::intcode::Automaton;
+use aoc_2019_rust::intcode::scheduler::{Policy, Rule, Scheduler, Stop};
+use aoc_2019_rust::read_input;
+
+fn main() {
+    println!("Part 1");
+    let start_1 = Instant::now();
+    part1();
+    let duration_1 = start_1.elapsed();
+    println!("- took {} micro secs", duration_1.as_micros());
+
+    println!();
+
+    println!("Part 2");
+    let start_2 = Instant::now();
+    part2();
+    let duration_2 = start_2.elapsed();
+    println!("- took {} micro secs", duration_2.as_micros());
+}
+
+const COMPUTERS: usize = 50;
+const NAT_ADDRESS: i128 = 255;
+
+fn part1() {
+    let program = read_input("inputs/day23.txt");
+    let mut nat = Nat::new(boot_network(program.trim(), COMPUTERS));
+    let (_, y) = nat.first_packet().expect("Expected a packet for the NAT");
+    println!("{y}");
+}
+
+fn part2() {
+    let program = read_input("inputs/day23.txt");
+    let mut nat = Nat::new(boot_network(program.trim(), COMPUTERS));
+    let y = nat.first_repeated_wake_up().expect("Expected the NAT to repeat itself");
+    println!("{y}");
+}
+
+/// Boots one computer per network address; each sends `(destination, x, y)` packets.
+fn boot_network(program: &str, computers: usize) -> Scheduler {
+    let mut network = Scheduler::new(Policy::UntilBlocked).with_empty_input(-1);
+    for address in 0..computers {
+        network.add(Automaton::new_with_program(program).add_initial_input(address as i128));
+        network.route(address, Rule::Addressed { size: 3 });
+    }
+    network
+}
+
+struct Nat {
+    network: Scheduler,
+    packet: Option<(i128, i128)>,
+    last_sent_y: Option<i128>,
+}
+
+impl Nat {
+    fn new(network: Scheduler) -> Self {
+        Self {
+            network,
+            packet: None,
+            last_sent_y: None,
+        }
+    }
+
+    /// Runs the network until it is idle, keeping the last packet sent to the NAT.
+    /// Returns the first packet it received while doing so, if any.
+    fn receive(&mut self) -> Option<(i128, i128)> {
+        let mut first = None;
+        loop {
+            match self.network.run().expect("Intcode program failed") {
+                Stop::Undeliverable => {
+                    for packet in self.network.take_undeliverable() {
+                        if let (NAT_ADDRESS, &[x, y]) = (packet.destination, packet.values.as_slice()) {
+                            first.get_or_insert((x, y));
+                            self.packet = Some((x, y));
+                        }
+                    }
+                }
+                Stop::Idle => return first,
+                Stop::AllHalted => panic!("All computers halted"),
+            }
+        }
+    }
+
+    fn first_packet(&mut self) -> Option<(i128, i128)> {
+        self.receive()
+    }
+
+    /// Wakes the idle network by sending the last packet to address 0, until
+    /// it sends the same y value twice in a row.
+    fn first_repeated_wake_up(&mut self) -> Option<i128> {
+        loop {
+            self.receive();
+            let (x, y) = self.packet?;
+            if self.last_sent_y == Some(y) {
+                return Some(y);
+            }
+            self.network.send(0, &[x, y]);
+            self.last_sent_y = Some(y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aoc_2019_rust::intcode::asm::assemble;
+    use crate::{boot_network, Nat};
+
+    // sends (address + 1, address, address + 100) on boot and echoes every
+    // packet it receives to the NAT
+    const ECHO: &str = "
+            in -> address
+            add address, #1 -> x
+            add address, #100 -> y
+            out x
+            out address
+            out y
+    loop:   in -> x
+            lt x, #0 -> empty
+            jt empty, #loop
+            in -> y
+            out #255
+            out x
+            out y
+            jt #1, #loop
+    address: data 0
+    x:      data 0
+    y:      data 0
+    empty:  data 0
+    ";
+
+    #[test]
+    fn test_first_packet() {
+        let program = assemble(ECHO).unwrap();
+        let mut nat = Nat::new(boot_network(&program, 3));
+        assert_eq!(nat.first_packet(), Some((0, 100)));
+    }
+
+    #[test]
+    fn test_first_repeated_wake_up() {
+        let program = assemble(ECHO).unwrap();
+        let mut nat = Nat::new(boot_network(&program, 3));
+        assert_eq!(nat.first_repeated_wake_up(), Some(101));
+    }
+}
@@ -21,8 +21,8 @@ fn main() {
 fn part1() {
     let input = read_input("inputs/day5.txt");
 
-    let mut automaton = Automaton::new().init().load(input.as_str()).add_initial_input(1);
-    automaton.run();
+    let mut automaton = Automaton::new_with_program(input.as_str()).add_initial_input(1);
+    automaton.run().expect("Intcode program failed");
     let output = automaton.get_last_output();
     println!("{output}");
 }
@@ -30,8 +30,8 @@ fn part1() {
 fn part2() {
     let input = read_input("inputs/day5.txt");
 
-    let mut automaton = Automaton::new().init().load(input.as_str()).add_initial_input(5);
-    automaton.run();
+    let mut automaton = Automaton::new_with_program(input.as_str()).add_initial_input(5);
+    automaton.run().expect("Intcode program failed");
     let output = automaton.get_last_output();
     println!("{output}");
 }
\ No newline at end of file
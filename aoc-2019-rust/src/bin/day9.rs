@@ -22,7 +22,7 @@ fn part1() {
     let input = read_input("inputs/day9.txt");
     let mut automaton = Automaton::new_with_program(input.as_str())
         .add_initial_input(1);
-    automaton.run();
+    automaton.run().expect("Intcode program failed");
     let keycode = automaton.get_last_output();
     println!("{keycode}");
 }
@@ -31,7 +31,7 @@ fn part2() {
     let input = read_input("inputs/day9.txt");
     let mut automaton = Automaton::new_with_program(input.as_str())
         .add_initial_input(2);
-    automaton.run();
+    automaton.run().expect("Intcode program failed");
     let coordinates = automaton.get_last_output();
     println!("{coordinates}");
 }
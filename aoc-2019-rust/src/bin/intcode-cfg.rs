@@ -0,0 +1,23 @@
+use std::process;
+use aoc_2019_rust::intcode::Automaton;
+use aoc_2019_rust::intcode::analysis::analyze;
+use aoc_2019_rust::intcode::program::Program;
+use aoc_2019_rust::read_arg;
+
+// Usage: cargo run --bin intcode-cfg -- inputs/day9.txt | dot -Tsvg > day9.svg
+// Prints the control-flow graph in Graphviz DOT, and the findings to stderr.
+fn main() {
+    let program : Program = match read_arg().parse() {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("Could not parse program: {error}");
+            process::exit(1);
+        }
+    };
+    let graph = analyze(&Automaton::from_program(&program));
+
+    for finding in &graph.findings {
+        eprintln!("{finding}");
+    }
+    print!("{}", graph.to_dot());
+}
@@ -0,0 +1,214 @@
+use std::io::{self, BufRead, Write};
+use std::process;
+use aoc_2019_rust::intcode::{Automaton, RunState};
+use aoc_2019_rust::intcode::disasm::disassemble_at;
+use aoc_2019_rust::intcode::program::Program;
+use aoc_2019_rust::read_arg;
+
+// Usage: cargo run --bin intcode-dbg -- inputs/day15.txt
+fn main() {
+    let program : Program = match read_arg().parse() {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("Could not parse program: {error}");
+            process::exit(1);
+        }
+    };
+    let mut debugger = Debugger::new(Automaton::from_program(&program));
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    debugger.show_current(&mut stdout).expect("Could not write to stdout");
+    loop {
+        print!("(dbg) ");
+        stdout.flush().expect("Could not write to stdout");
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).expect("Could not read from stdin") == 0 {
+            break;
+        }
+        match debugger.execute(line.trim(), &mut stdout) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(error) => panic!("Could not write to stdout: {error}"),
+        }
+    }
+}
+
+const HELP: &str = "\
+s [n]          step n instructions (default 1)
+c              continue until halt, input, breakpoint or watchpoint
+b <pc>         set a breakpoint           db <pc>    delete it
+w <address>    watch writes to an address dw <address> delete it
+m <addr> [n]   show n memory words (default 8)
+r              show pc, relative base, state and queued input
+l [addr] [n]   list n instructions from addr (default: 10 from pc)
+i <v> [v ...]  queue input values
+o              show outputs not shown before
+q              quit";
+
+struct Debugger {
+    automaton: Automaton,
+}
+
+impl Debugger {
+    fn new(automaton: Automaton) -> Self {
+        Self { automaton }
+    }
+
+    /// Executes one command line. Returns false when the session should end.
+    fn execute(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let args : Result<Vec<i128>, _> = words.map(|word| word.parse::<i128>()).collect();
+        let Ok(args) = args else {
+            writeln!(out, "arguments must be numbers")?;
+            return Ok(true);
+        };
+        let arg = |index: usize| args.get(index).and_then(|&arg| u128::try_from(arg).ok());
+
+        match (command, arg(0)) {
+            ("", _) => {}
+            ("q", _) => return Ok(false),
+            ("h", _) => writeln!(out, "{HELP}")?,
+            ("s", _) => {
+                let steps = arg(0).unwrap_or(1);
+                for _ in 0..steps {
+                    match self.automaton.step() {
+                        Ok(RunState::Running) => {}
+                        outcome => {
+                            self.report(outcome, out)?;
+                            break;
+                        }
+                    }
+                }
+                self.show_outputs(out)?;
+                self.show_current(out)?;
+            }
+            ("c", _) => {
+                let outcome = self.automaton.run();
+                self.show_outputs(out)?;
+                self.report(outcome, out)?;
+                self.show_current(out)?;
+            }
+            ("b", Some(pc)) => self.automaton.add_breakpoint(pc),
+            ("db", Some(pc)) => {
+                if !self.automaton.remove_breakpoint(pc) {
+                    writeln!(out, "no breakpoint at {pc}")?;
+                }
+            }
+            ("w", Some(address)) => self.automaton.add_watchpoint(address),
+            ("dw", Some(address)) => {
+                if !self.automaton.remove_watchpoint(address) {
+                    writeln!(out, "no watchpoint on {address}")?;
+                }
+            }
+            ("m", Some(from)) => match from.checked_add(arg(1).unwrap_or(8)) {
+                Some(to) => {
+                    for address in from..to {
+                        writeln!(out, "{address:>6}  {}", self.automaton.memory().read(address))?;
+                    }
+                }
+                None => writeln!(out, "range runs past the last address")?,
+            },
+            ("r", _) => {
+                writeln!(out, "pc {}  relative base {}  state {:?}", self.automaton.pc, self.automaton.relative_base, self.automaton.state())?;
+                writeln!(out, "queued input {:?}", self.automaton.input)?;
+            }
+            ("l", _) => {
+                let mut address = arg(0).unwrap_or(self.automaton.pc);
+                for _ in 0..arg(1).unwrap_or(10) {
+                    let line = disassemble_at(&self.automaton, address);
+                    writeln!(out, "{line}")?;
+                    let Some(next) = address.checked_add(line.size()) else {
+                        writeln!(out, "reached the last address")?;
+                        break;
+                    };
+                    address = next;
+                }
+            }
+            ("i", _) if !args.is_empty() => {
+                for &value in &args {
+                    if let Err(error) = self.automaton.runtime_input(value) {
+                        writeln!(out, "{error}")?;
+                        break;
+                    }
+                }
+            }
+            ("o", _) => self.show_outputs(out)?,
+            _ => writeln!(out, "unknown command '{line}', type h for help")?,
+        }
+        Ok(true)
+    }
+
+    fn report(&self, outcome: Result<RunState, impl std::fmt::Display>, out: &mut impl Write) -> io::Result<()> {
+        match outcome {
+            Ok(RunState::AwaitingInput) => writeln!(out, "awaiting input, queue values with i"),
+            Ok(RunState::Breakpoint) => match self.automaton.watch_hit() {
+                Some(address) => writeln!(out, "watchpoint: {address} = {}", self.automaton.memory().read(address)),
+                None => writeln!(out, "breakpoint at {}", self.automaton.pc),
+            },
+            Ok(state) => writeln!(out, "{state:?}"),
+            Err(error) => writeln!(out, "fault: {error}"),
+        }
+    }
+
+    fn show_current(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "{}", disassemble_at(&self.automaton, self.automaton.pc))
+    }
+
+    fn show_outputs(&mut self, out: &mut impl Write) -> io::Result<()> {
+        while let Some(output) = self.automaton.get_output(1) {
+            writeln!(out, "output: {}", output[0])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aoc_2019_rust::intcode::Automaton;
+    use crate::Debugger;
+
+    fn session(program: &str, commands: &[&str]) -> String {
+        let mut debugger = Debugger::new(Automaton::new_with_program(program));
+        let mut out = Vec::new();
+        for command in commands {
+            debugger.execute(command, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_breakpoint_and_input() {
+        let out = session("3,9,4,9,4,9,99,0,0,0", &["b 4", "c", "i -42", "c", "c"]);
+        let lines : Vec<&str> = out.lines().map(str::trim).collect();
+        assert_eq!(lines[0], "awaiting input, queue values with i");
+        assert!(lines[1].ends_with("IN -> @9"));
+        assert_eq!(lines[2], "output: -42");
+        assert_eq!(lines[3], "breakpoint at 4");
+        assert_eq!(lines[5], "output: -42");
+        assert_eq!(lines[6], "Halted");
+    }
+
+    #[test]
+    fn test_watchpoint_and_memory() {
+        let out = session("1101,1,2,7,99,0,0,0", &["w 7", "c", "m 6 2", "r"]);
+        let lines : Vec<&str> = out.lines().map(str::trim).collect();
+        assert_eq!(lines[0], "watchpoint: 7 = 3");
+        assert_eq!(lines[2], "6  0");
+        assert_eq!(lines[3], "7  3");
+        assert_eq!(lines[4], "pc 4  relative base 0  state Breakpoint");
+    }
+
+    #[test]
+    fn test_far_addresses() {
+        let max = i128::MAX as u128;
+        let out = session("99", &[&format!("m {max} 2"), &format!("l {max} 2")]);
+        let lines : Vec<&str> = out.lines().map(str::trim).collect();
+        assert_eq!(lines[0], format!("{max}  0"));
+        assert_eq!(lines[1], format!("{}  0", max + 1));
+        assert!(lines[2].starts_with(&max.to_string()) && lines[2].ends_with("DATA 0"));
+        assert!(lines[3].starts_with(&(max + 1).to_string()) && lines[3].ends_with("DATA 0"));
+    }
+}
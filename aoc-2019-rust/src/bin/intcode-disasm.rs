@@ -0,0 +1,21 @@
+use std::process;
+use aoc_2019_rust::intcode::Automaton;
+use aoc_2019_rust::intcode::disasm::disassemble;
+use aoc_2019_rust::intcode::program::Program;
+use aoc_2019_rust::read_arg;
+
+// Usage: cargo run --bin intcode-disasm -- inputs/day9.txt
+fn main() {
+    let program : Program = match read_arg().parse() {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("Could not parse program: {error}");
+            process::exit(1);
+        }
+    };
+    let automaton = Automaton::from_program(&program);
+
+    for line in disassemble(&automaton) {
+        println!("{line}");
+    }
+}
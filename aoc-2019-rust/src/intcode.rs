@@ -1,18 +1,88 @@
-use std::collections::{HashMap, VecDeque};
-use itertools::Itertools;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use crate::intcode::io::{InputSource, OutputSink};
+use crate::intcode::memory::{copy_memory, DenseMemory, Memory};
+use crate::intcode::program::Program;
+use crate::intcode::trace::Trace;
+use crate::intcode::word::{BigInt, FixedWord, Overflow, Word};
+
+pub mod analysis;
+pub mod ascii;
+pub mod asm;
+pub mod controller;
+pub mod disasm;
+pub mod frame;
+pub mod io;
+pub mod memory;
+pub mod network;
+pub mod program;
+pub mod scheduler;
+pub mod snapshot;
+pub mod solver;
+pub mod symbolic;
+pub mod trace;
+mod varint;
+pub mod word;
 
 #[allow(dead_code)]
+#[derive(Clone)]
 pub struct InstructionDef {
     opcode : i8,
     no_params : i8,
+    mnemonic : &'static str,
+    /// Whether the last parameter is the address the instruction writes to.
+    writes : bool,
 }
 
+/// Largest number of parameters taken by any instruction.
+const MAX_PARAMS: usize = 3;
+/// Instructions at addresses below this limit are cached after decoding.
+const DECODE_CACHE_LIMIT: u128 = 1 << 16;
+/// Most words returned by `Automaton::dump_memory`.
+pub const DUMP_LIMIT: u128 = 1 << 20;
+
+#[derive(Debug, Copy, Clone)]
 pub struct Instruction {
     opcode : i8,
-    params : Vec<Parameter>,
+    no_params : usize,
+    params : [Parameter; MAX_PARAMS],
+}
+
+impl Instruction {
+    fn len(&self) -> u128 {
+        self.no_params as u128 + 1
+    }
 }
 
-#[derive(Debug)]
+/// Decodes the instruction whose first word, at `pc`, is `val`.
+fn decode_value(instruction_set: &HashMap<i8, InstructionDef>, pc: u128, val: i128) -> Result<Instruction, IntcodeError> {
+    if !(1..=99999).contains(&val) {
+        return Err(IntcodeError::InvalidOpcode { pc, opcode: val });
+    }
+
+    let opcode = (val % 100) as i8;
+    let no_params = instruction_set.get(&opcode)
+        .map(|def| def.no_params as usize)
+        .ok_or(IntcodeError::InvalidOpcode { pc, opcode: val })?;
+    let mut params = [Parameter { address: 0, mode: ParameterMode::Position }; MAX_PARAMS];
+    let mut modes = val / 100;
+    for (i, param) in params.iter_mut().take(no_params).enumerate() {
+        param.address = pc + i as u128 + 1;
+        param.mode = ParameterMode::try_from(modes % 10)
+            .map_err(|mode| IntcodeError::InvalidParameterMode { pc, mode })?;
+        modes /= 10;
+    }
+
+    Ok(Instruction {
+        opcode,
+        no_params,
+        params,
+    })
+}
+
+#[derive(Debug, Copy, Clone)]
 pub struct Parameter {
     address: u128,
     mode : ParameterMode,
@@ -25,367 +95,791 @@ pub enum ParameterMode {
     Relative = 2,
 }
 
-pub struct Automaton {
+impl TryFrom<i128> for ParameterMode {
+    type Error = i128;
+
+    fn try_from(value: i128) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ParameterMode::Position),
+            1 => Ok(ParameterMode::Immediate),
+            2 => Ok(ParameterMode::Relative),
+            invalid => Err(invalid),
+        }
+    }
+}
+
+/// Execution state of an `Automaton`. `run` and `step` report the state the
+/// machine stopped in, so controllers can match on it instead of polling flags.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RunState {
+    /// Ready to execute the next instruction.
+    Running,
+    /// Blocked on an input instruction with an empty input queue.
+    AwaitingInput,
+    /// Executed opcode 99; the machine cannot be resumed.
+    Halted,
+    /// Stopped on an `IntcodeError`; the machine cannot be resumed.
+    Faulted,
+    /// An `ADD` or `MUL` overflowed the word type under `Overflow::Promote`;
+    /// `promote` continues the program on wider words.
+    Overflowed,
+    /// Stopped because the instruction budget or time limit of a bounded run
+    /// was used up. The next run continues where this one stopped.
+    StepLimitReached,
+    /// Stopped before executing an instruction at a breakpoint.
+    Breakpoint,
+}
+
+pub type RunOutcome = Result<RunState, IntcodeError>;
+
+/// Errors raised while decoding or executing an Intcode program.
+/// Each variant records the program counter of the faulting instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError {
+    InvalidOpcode { pc: u128, opcode: i128 },
+    InvalidParameterMode { pc: u128, mode: i128 },
+    WriteToImmediate { pc: u128 },
+    NegativeAddress { pc: u128, address: i128 },
+    MissingOperand { pc: u128, index: usize },
+    IllegalTransition { pc: u128, state: RunState },
+    IncompleteFrame { pc: u128, size: usize, found: usize },
+    AddressOutOfRange { pc: u128, address: u128, limit: u128 },
+    WriteToProtected { pc: u128, address: u128 },
+    Overflow { pc: u128 },
+    /// The relative base, or an address relative to it, does not fit in an `i128`.
+    RelativeBaseOverflow { pc: u128 },
+    /// A word used as an address, opcode or `i128` output does not fit an `i128`.
+    WordOutOfRange { pc: u128 },
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntcodeError::InvalidOpcode { pc, opcode } => write!(f, "invalid opcode {opcode} at pc {pc}"),
+            IntcodeError::InvalidParameterMode { pc, mode } => write!(f, "unknown parameter mode {mode} at pc {pc}"),
+            IntcodeError::WriteToImmediate { pc } => write!(f, "write to immediate-mode parameter at pc {pc}"),
+            IntcodeError::NegativeAddress { pc, address } => write!(f, "negative address {address} at pc {pc}"),
+            IntcodeError::MissingOperand { pc, index } => write!(f, "missing operand {index} at pc {pc}"),
+            IntcodeError::IllegalTransition { pc, state } => write!(f, "machine at pc {pc} cannot resume from state {state:?}"),
+            IntcodeError::AddressOutOfRange { pc, address, limit } => write!(f, "address {address} beyond memory limit {limit} at pc {pc}"),
+            IntcodeError::WriteToProtected { pc, address } => write!(f, "write to protected program address {address} at pc {pc}"),
+            IntcodeError::Overflow { pc } => write!(f, "arithmetic overflow at pc {pc}"),
+            IntcodeError::RelativeBaseOverflow { pc } => write!(f, "relative base overflow at pc {pc}"),
+            IntcodeError::WordOutOfRange { pc } => write!(f, "word does not fit an i128 at pc {pc}"),
+            IntcodeError::IncompleteFrame { pc, size, found } => write!(f, "halted at pc {pc} with {found} values of a {size}-value output frame"),
+        }
+    }
+}
+
+impl std::error::Error for IntcodeError {}
+
+pub struct Automaton<W: Word = i128> {
     pub instruction_set : HashMap<i8, InstructionDef>,
     pub pc : u128,
-    pub halted: bool,
-    pub blocked : bool,
-    pub memory : HashMap<u128,i128>,
-    pub input : VecDeque<i128>,
-    pub output : VecDeque<i128>,
+    state: RunState,
+    fault: Option<IntcodeError>,
+    memory : Box<dyn Memory<W>>,
+    // the memory as built, patches included, for `reset`
+    image : Box<dyn Memory<W>>,
+    decoded : Vec<Option<Instruction>>,
+    pub input : VecDeque<W>,
+    pub output : VecDeque<W>,
+    input_source : Option<Box<dyn InputSource>>,
+    output_sink : Option<Box<dyn OutputSink>>,
     pub output_curr_index : usize,
     pub relative_base: i128,
+    breakpoints : HashSet<u128>,
+    watchpoints : HashSet<u128>,
+    watch_hit : Option<u128>,
+    trace : Option<Trace>,
+    instructions : u64,
+    memory_limit : Option<u128>,
+    protected : u128,
+    overflow : Overflow,
+}
+
+impl Default for Automaton {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Word> Clone for Automaton<W> {
+    fn clone(&self) -> Self {
+        self.fork()
+    }
 }
 
 impl Automaton {
     pub fn new() -> Self {
+        Self::blank()
+    }
+
+    pub fn new_with_program(program: &str) -> Self {
+        Self::new_with_words(program)
+    }
+}
+
+impl<W: Word> Automaton<W> {
+    /// A machine with another word type than the default `i128`, e.g.
+    /// `Automaton::<i64>::new_with_words(program)`. Panics if `program` does
+    /// not parse; parse a `Program` to handle the error instead.
+    pub fn new_with_words(program: &str) -> Self {
+        match program.parse::<Program<W>>() {
+            Ok(program) => Self::from_program(&program),
+            Err(error) => panic!("Invalid Intcode program: {error}"),
+        }
+    }
+
+    fn blank() -> Self {
         let mut automaton = Self {
             instruction_set: HashMap::new(),
             pc: 0,
-            halted: false,
-            blocked: false,
-            memory: HashMap::new(),
+            state: RunState::Running,
+            fault: None,
+            memory: Box::<DenseMemory<W>>::default(),
+            image: Box::<DenseMemory<W>>::default(),
+            decoded: Vec::new(),
             input: VecDeque::new(),
             output: VecDeque::new(),
+            input_source: None,
+            output_sink: None,
             output_curr_index: 0,
             relative_base: 0,
+            breakpoints: HashSet::new(),
+            watchpoints: HashSet::new(),
+            watch_hit: None,
+            trace: None,
+            instructions: 0,
+            memory_limit: None,
+            protected: 0,
+            overflow: Overflow::default(),
         };
 
         automaton.init();
         automaton
     }
 
-    pub fn new_with_program(program: &str) -> Self {
-        let automaton = Automaton::new();
-        automaton.load(program)
+    /// Moves the current memory contents into another backend, e.g.
+    /// `Automaton::new_with_program(program).with_memory(SparseMemory::new())`.
+    pub fn with_memory<M: Memory<W> + 'static>(mut self, mut memory: M) -> Self {
+        copy_memory(self.memory.as_ref(), &mut memory, |value| value);
+        self.memory = Box::new(memory);
+        self.image = self.memory.fork();
+
+        self
+    }
+
+    /// Faults with `AddressOutOfRange` when the program reads, writes or
+    /// executes at `limit` or beyond.
+    pub fn with_memory_limit(mut self, limit: u128) -> Self {
+        self.memory_limit = Some(limit);
+
+        self
+    }
+
+    /// Makes the memory loaded so far read-only: a write to it faults with
+    /// `WriteToProtected` instead of modifying the program.
+    pub fn with_protected_program(mut self) -> Self {
+        self.protected = self.memory.len();
+
+        self
+    }
+
+    /// Sets what `ADD` and `MUL` do when their result does not fit the word
+    /// type; the default is `Overflow::Fault`.
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+
+        self
+    }
+
+    /// Writes `value` to `address` of the image the machine starts from,
+    /// e.g. `Automaton::from_program(&program).with_patch(0, 2)`. Unlike
+    /// `poke`, the patch survives `reset`.
+    pub fn with_patch(mut self, address: u128, value: W) -> Self {
+        self.poke(address, value.clone());
+        self.image.write(address, value);
+
+        self
+    }
+
+    pub fn memory(&self) -> &dyn Memory<W> {
+        self.memory.as_ref()
+    }
+
+    pub fn peek(&self, address: u128) -> W {
+        self.memory.read(address)
+    }
+
+    /// Writes `value` to `address` like a debugger would: protection and
+    /// watchpoints do not apply.
+    pub fn poke(&mut self, address: u128, value: W) {
+        self.memory.write(address, value);
+        self.invalidate_decoded(address);
+    }
+
+    /// Restores the memory the machine was built with, patches included,
+    /// and starts over at pc 0 with empty input and output. Breakpoints,
+    /// watchpoints, I/O sources and settings are kept. With the default
+    /// memory backend this only copies the pages the run wrote to.
+    pub fn reset(&mut self) {
+        self.memory = self.image.fork();
+        self.decoded.clear();
+        self.pc = 0;
+        self.state = RunState::Running;
+        self.fault = None;
+        self.input.clear();
+        self.output.clear();
+        self.output_curr_index = 0;
+        self.relative_base = 0;
+        self.watch_hit = None;
+        self.instructions = 0;
+        if let Some(trace) = self.trace.as_mut() {
+            *trace = Trace::default();
+        }
+    }
+
+    /// An independent machine in the same state. With the default memory
+    /// backend both machines share untouched memory pages copy-on-write, so
+    /// forking costs little more than copying the input and output queues.
+    /// An input source or output sink is not shared; the fork uses its buffers.
+    pub fn fork(&self) -> Automaton<W> {
+        Automaton {
+            instruction_set: self.instruction_set.clone(),
+            pc: self.pc,
+            state: self.state,
+            fault: self.fault.clone(),
+            memory: self.memory.fork(),
+            image: self.image.fork(),
+            decoded: self.decoded.clone(),
+            input: self.input.clone(),
+            output: self.output.clone(),
+            input_source: None,
+            output_sink: None,
+            output_curr_index: self.output_curr_index,
+            relative_base: self.relative_base,
+            breakpoints: self.breakpoints.clone(),
+            watchpoints: self.watchpoints.clone(),
+            watch_hit: self.watch_hit,
+            trace: self.trace.clone(),
+            instructions: self.instructions,
+            memory_limit: self.memory_limit,
+            protected: self.protected,
+            overflow: self.overflow,
+        }
     }
 
     fn init(&mut self) {
-        self.instruction_set.insert(1, InstructionDef {opcode : 1, no_params : 3});
-        self.instruction_set.insert(2, InstructionDef {opcode : 2, no_params : 3});
-        self.instruction_set.insert(3, InstructionDef {opcode : 3, no_params : 1});
-        self.instruction_set.insert(4, InstructionDef {opcode : 4, no_params : 1});
+        self.instruction_set.insert(1, InstructionDef {opcode : 1, no_params : 3, mnemonic : "ADD", writes : true});
+        self.instruction_set.insert(2, InstructionDef {opcode : 2, no_params : 3, mnemonic : "MUL", writes : true});
+        self.instruction_set.insert(3, InstructionDef {opcode : 3, no_params : 1, mnemonic : "IN", writes : true});
+        self.instruction_set.insert(4, InstructionDef {opcode : 4, no_params : 1, mnemonic : "OUT", writes : false});
 
-        self.instruction_set.insert(5, InstructionDef {opcode : 5, no_params : 2});
-        self.instruction_set.insert(6, InstructionDef {opcode : 6, no_params : 2});
-        self.instruction_set.insert(7, InstructionDef {opcode : 7, no_params : 3});
-        self.instruction_set.insert(8, InstructionDef {opcode : 8, no_params : 3});
-        self.instruction_set.insert(9, InstructionDef {opcode : 9, no_params : 1});
+        self.instruction_set.insert(5, InstructionDef {opcode : 5, no_params : 2, mnemonic : "JT", writes : false});
+        self.instruction_set.insert(6, InstructionDef {opcode : 6, no_params : 2, mnemonic : "JF", writes : false});
+        self.instruction_set.insert(7, InstructionDef {opcode : 7, no_params : 3, mnemonic : "LT", writes : true});
+        self.instruction_set.insert(8, InstructionDef {opcode : 8, no_params : 3, mnemonic : "EQ", writes : true});
+        self.instruction_set.insert(9, InstructionDef {opcode : 9, no_params : 1, mnemonic : "ARB", writes : false});
 
-        self.instruction_set.insert(99, InstructionDef {opcode : 99, no_params : 0});
+        self.instruction_set.insert(99, InstructionDef {opcode : 99, no_params : 0, mnemonic : "HLT", writes : false});
 
         self.input.clear();
     }
 
-    fn load(mut self, input : &str) -> Self {
-        input.split(",")
-            .filter_map(|w| w.parse().ok())
-            .enumerate()
-            .for_each(|(i, instr)| {
-                self.memory.insert(i as u128, instr);
-            });
+    fn decode(&mut self) -> Result<Instruction, IntcodeError> {
+        self.check_limit(self.pc)?;
+        if self.pc < DECODE_CACHE_LIMIT {
+            if let Some(Some(instruction)) = self.decoded.get(self.pc as usize) {
+                return Ok(*instruction);
+            }
+        }
+
+        let instruction = self.decode_at(self.pc)?;
+        if self.pc < DECODE_CACHE_LIMIT {
+            let index = self.pc as usize;
+            if index >= self.decoded.len() {
+                self.decoded.resize(index + 1, None);
+            }
+            self.decoded[index] = Some(instruction);
+        }
+        Ok(instruction)
+    }
+
+    /// Splits the word at `pc` into its opcode (lowest two digits) and one
+    /// mode digit per parameter, from the hundreds digit upwards.
+    fn decode_at(&self, pc: u128) -> Result<Instruction, IntcodeError> {
+        let value = self.to_i128(&self.read_from_address(pc))?;
+        decode_value(&self.instruction_set, pc, value)
+    }
 
-        self
+    pub(crate) fn to_i128(&self, value: &W) -> Result<i128, IntcodeError> {
+        value.to_i128().ok_or(IntcodeError::WordOutOfRange { pc: self.pc })
     }
 
-    fn decode(&self) -> Instruction {
-        let val = self.read_value(self.pc, ParameterMode::Immediate) as i128;
-        match val {
-            1..=99 => self.decode_default(&val),
-            100..=99999 => self.decode_extended(&val),
-            invalid => panic!("Invalid opcode: {invalid}")
+    /// Drops cached instructions that span `address`.
+    fn invalidate_decoded(&mut self, address: u128) {
+        if address < self.decoded.len() as u128 {
+            let from = address.saturating_sub(MAX_PARAMS as u128) as usize;
+            let to = (address as usize).min(self.decoded.len() - 1);
+            self.decoded[from..=to].fill(None);
         }
     }
 
-    fn decode_default(&self, val: &i128) -> Instruction {
-        let opcode = *val as i8;
-        let num_params = self.instruction_set.get(&opcode).unwrap().no_params as u128;
-        let mut params = Vec::new();
-        for i in 1..=num_params {
-            params.push(Parameter {
-                address: self.pc + i,
-                mode : ParameterMode::Position,
-            });
-        };
-        Instruction {
-            opcode,
-            params,
+    pub fn state(&self) -> RunState {
+        self.state
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.state == RunState::Halted
+    }
+
+    /// Executes instructions until the machine halts, blocks on input or faults,
+    /// reaches a breakpoint or writes to a watched address.
+    pub fn run(&mut self) -> RunOutcome {
+        self.run_limited(|| false)
+    }
+
+    /// Like `run`, but executes at most `steps` instructions. Stops with
+    /// `RunState::StepLimitReached` when they are used up.
+    pub fn run_for(&mut self, steps: u64) -> RunOutcome {
+        let mut remaining = steps;
+        self.run_limited(|| {
+            if remaining == 0 {
+                return true;
+            }
+            remaining -= 1;
+            false
+        })
+    }
+
+    /// Like `run`, but stops with `RunState::StepLimitReached` once `timeout`
+    /// has passed. The clock is only read every few thousand instructions.
+    pub fn run_with_timeout(&mut self, timeout: Duration) -> RunOutcome {
+        const CLOCK_INTERVAL: u32 = 4096;
+        let deadline = Instant::now() + timeout;
+        let mut countdown = 0;
+        self.run_limited(|| {
+            if countdown == 0 {
+                countdown = CLOCK_INTERVAL;
+                if Instant::now() >= deadline {
+                    return true;
+                }
+            }
+            countdown -= 1;
+            false
+        })
+    }
+
+    /// Number of instructions executed since the machine was created. A blocked
+    /// input instruction is counted once it has read its value.
+    pub fn instructions_executed(&self) -> u64 {
+        self.instructions
+    }
+
+    fn run_limited(&mut self, mut exhausted: impl FnMut() -> bool) -> RunOutcome {
+        self.resume()?;
+        let mut first = true;
+        while self.state == RunState::Running {
+            if !first && !self.breakpoints.is_empty() && self.breakpoints.contains(&self.pc) {
+                self.state = RunState::Breakpoint;
+                break;
+            }
+            if exhausted() {
+                self.state = RunState::StepLimitReached;
+                break;
+            }
+            first = false;
+
+            self.execute_instruction()?;
+            if self.watch_hit.is_some() && self.state == RunState::Running {
+                self.state = RunState::Breakpoint;
+            }
         }
+        Ok(self.state)
     }
 
-    fn decode_extended(&self, val: &i128) -> Instruction {
-        let mut op_extended = val.to_string().chars().rev().collect::<String>();
-        while op_extended.len() < 5 {
-            op_extended.push('0');
+    /// Executes a single instruction. Returns `RunState::Running` when the
+    /// machine can continue.
+    pub fn step(&mut self) -> RunOutcome {
+        self.resume()?;
+        if self.state == RunState::Running {
+            self.execute_instruction()?;
         }
-        let op_extended = op_extended.chars().rev().collect::<String>();
-        let opcode = &op_extended[3..=4].parse::<i8>().ok().unwrap();
-
-        let mut params = Vec::new();
-        for i in 1..=(self.instruction_set.get(&opcode).unwrap().no_params as u128) {
-            let index = 3-(i as usize);
-            let param_mode = &op_extended[index..=index].parse().ok().unwrap();
-            let address = self.pc + i;
-
-            params.push(Parameter {
-                address,
-                mode : match param_mode {
-                    0 => ParameterMode::Position,
-                    1 => ParameterMode::Immediate,
-                    2 => ParameterMode::Relative,
-                    _ => panic!("unknown parameter mode"),
-                },
-            });
-        };
-        Instruction {
-            opcode: *opcode,
-            params,
+        Ok(self.state)
+    }
+
+    fn resume(&mut self) -> Result<(), IntcodeError> {
+        match self.state {
+            RunState::Halted | RunState::Overflowed => {}
+            RunState::Faulted => return Err(self.fault.clone()
+                .unwrap_or(IntcodeError::IllegalTransition { pc: self.pc, state: self.state })),
+            RunState::AwaitingInput if !self.can_read_input() => {}
+            _ => self.state = RunState::Running,
         }
+        Ok(())
+    }
+
+    /// Makes `run` stop with `RunState::Breakpoint` before executing the
+    /// instruction at `pc`. A run that starts at `pc` executes it.
+    pub fn add_breakpoint(&mut self, pc: u128) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: u128) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    /// Makes `run` stop with `RunState::Breakpoint` after an instruction
+    /// writes to `address`.
+    pub fn add_watchpoint(&mut self, address: u128) {
+        self.watchpoints.insert(address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u128) -> bool {
+        self.watchpoints.remove(&address)
     }
 
-    pub fn run(&mut self) {
-        while !self.halted && !self.blocked {
-            let instruction = self.decode();
-            self.do_operation(&instruction);
+    /// The watched address written by the last executed instruction, if any.
+    pub fn watch_hit(&self) -> Option<u128> {
+        self.watch_hit
+    }
+
+    fn execute_instruction(&mut self) -> Result<(), IntcodeError> {
+        self.watch_hit = None;
+        let result = self.decode().and_then(|instruction| {
+            let pending = match self.trace {
+                Some(_) => Some(self.trace_before(&instruction)?),
+                None => None,
+            };
+            self.do_operation(&instruction)?;
+            if !matches!(self.state, RunState::AwaitingInput | RunState::Overflowed) {
+                self.instructions += 1;
+            }
+            if let Some(pending) = pending {
+                self.trace_after(pending)?;
+            }
+            Ok(())
+        });
+        if let Err(error) = &result {
+            self.state = RunState::Faulted;
+            self.fault = Some(error.clone());
         }
+        result
     }
 
-    pub fn dump_memory(&self) -> Vec<i128> {
-        let mem : Vec<i128> = self.memory.keys()
-            .sorted()
-            .map(|key| self.memory.get(key))
-            .filter(|opt|opt.is_some())
-            .map(|opt|opt.unwrap())
-            .map(|val| val.clone())
-            .collect();
-        mem
+    /// The memory image from address 0 up to the highest written address,
+    /// with 0 for cells that were never written. Stops after `DUMP_LIMIT`
+    /// words; use `dump_range` to page through memory beyond it.
+    pub fn dump_memory(&self) -> Vec<W> {
+        self.dump_range(0..self.memory.len().min(DUMP_LIMIT))
     }
 
-    pub fn get_last_output(&self) -> i128 {
-        self.output.iter().last().unwrap().clone()
+    /// The words at the addresses in `range`.
+    pub fn dump_range(&self, range: Range<u128>) -> Vec<W> {
+        range.map(|address| self.memory.read(address)).collect()
     }
 
-    pub fn get_last_2_outputs(&self) -> (i128,i128) {
-        (
-            self.output.get(self.output.len().saturating_sub(2)).unwrap().clone(),
-            self.output.iter().last().unwrap().clone()
-        )
+    pub fn get_last_output(&self) -> W {
+        self.output.iter().last().unwrap().clone()
     }
 
     pub fn has_output(&self) -> bool {
         !self.output.is_empty() && (self.output.len() > self.output_curr_index)
     }
 
-    pub fn get_output(&mut self, n: usize) -> Option<Vec<i128>> {
+    pub fn get_output(&mut self, n: usize) -> Option<Vec<W>> {
         if (self.output_curr_index + n) > self.output.len() {
             None
         } else {
-            let out = self.output.iter().skip(self.output_curr_index).take(n).map(|&entry| entry).collect();
+            let out = self.output.iter().skip(self.output_curr_index).take(n).cloned().collect();
             self.output_curr_index += n;
             Some(out)
         }
     }
 
-    pub fn add_initial_input(mut self, input : i128) -> Self {
+    pub fn add_initial_input(mut self, input : W) -> Self {
         self.input.push_back(input);
 
         self
     }
 
-    pub fn runtime_input(&mut self, input: i128) {
-        self.input.push_back(input);
-        self.blocked = false;
-    }
-
-    fn has_input(&mut self) -> bool {
-        !self.input.is_empty()
-    }
-
-    fn read_input(&mut self) -> i128 {
-        self.input.pop_front().expect("Reached end of input")
+    /// Queues an input value. Rejected once the machine has halted or faulted.
+    pub fn runtime_input(&mut self, input: W) -> Result<(), IntcodeError> {
+        match self.state {
+            RunState::Halted | RunState::Faulted => {
+                Err(IntcodeError::IllegalTransition { pc: self.pc, state: self.state })
+            }
+            _ => {
+                self.input.push_back(input);
+                Ok(())
+            }
+        }
     }
 
-    fn write_value(&mut self, value: i128, write_parameter: u128, mode: ParameterMode) {
-        let address = self.parameter_to_address(write_parameter, mode) as u128;
-        self.memory.insert(address, value);
+    fn write_value(&mut self, value: W, write_parameter: u128, mode: ParameterMode) -> Result<(), IntcodeError> {
+        if let ParameterMode::Immediate = mode {
+            return Err(IntcodeError::WriteToImmediate { pc: self.pc });
+        }
+        let address = self.parameter_to_address(write_parameter, mode)?;
+        if address < self.protected {
+            return Err(IntcodeError::WriteToProtected { pc: self.pc, address });
+        }
+        self.memory.write(address, value);
+        self.invalidate_decoded(address);
+        if !self.watchpoints.is_empty() && self.watchpoints.contains(&address) {
+            self.watch_hit = Some(address);
+        }
+        Ok(())
     }
 
-    fn read_value(&self, read_parameter: u128, mode: ParameterMode) -> i128 {
-        let address = self.parameter_to_address(read_parameter, mode);
-        let value = self.read_from_address(address);
-        value
+    fn read_value(&self, read_parameter: u128, mode: ParameterMode) -> Result<W, IntcodeError> {
+        let address = self.parameter_to_address(read_parameter, mode)?;
+        Ok(self.read_from_address(address))
     }
 
-    fn parameter_to_address(&self, param_value: u128, mode: ParameterMode) -> u128 {
+    fn parameter_to_address(&self, param_value: u128, mode: ParameterMode) -> Result<u128, IntcodeError> {
         match mode {
             ParameterMode::Position => {
-                self.read_from_address(param_value) as u128
+                self.checked_address(self.to_i128(&self.read_from_address(param_value))?)
             }
             ParameterMode::Immediate => {
-                param_value
+                Ok(param_value)
             }
             ParameterMode::Relative => {
-                (self.read_from_address(param_value) + self.relative_base) as u128
+                let offset = self.to_i128(&self.read_from_address(param_value))?;
+                let address = offset.checked_add(self.relative_base)
+                    .ok_or(IntcodeError::RelativeBaseOverflow { pc: self.pc })?;
+                self.checked_address(address)
             }
         }
     }
 
-    fn read_from_address(&self, address: u128) -> i128 {
-        *self.memory.get(&address).unwrap_or(&0)
+    fn checked_address(&self, address: i128) -> Result<u128, IntcodeError> {
+        if address < 0 {
+            Err(IntcodeError::NegativeAddress { pc: self.pc, address })
+        } else {
+            self.check_limit(address as u128)
+        }
+    }
+
+    fn check_limit(&self, address: u128) -> Result<u128, IntcodeError> {
+        match self.memory_limit {
+            Some(limit) if address >= limit => Err(IntcodeError::AddressOutOfRange { pc: self.pc, address, limit }),
+            _ => Ok(address),
+        }
+    }
+
+    fn read_from_address(&self, address: u128) -> W {
+        self.memory.read(address)
+    }
+
+    fn param(&self, instr: &Instruction, index: usize) -> Result<Parameter, IntcodeError> {
+        instr.params[..instr.no_params].get(index)
+            .copied()
+            .ok_or(IntcodeError::MissingOperand { pc: self.pc, index })
     }
 
-    fn get_increment_for_opcode(&self, opcode : &i8) -> u128 {
-        (self.instruction_set.get(opcode).unwrap().no_params + 1) as u128
+    fn operand(&self, instr: &Instruction, index: usize) -> Result<W, IntcodeError> {
+        let param = self.param(instr, index)?;
+        self.read_value(param.address, param.mode)
     }
 
-    fn do_operation(&mut self, instruction : &Instruction) {
+    fn do_operation(&mut self, instruction : &Instruction) -> Result<(), IntcodeError> {
         let new_pc = match instruction.opcode {
-            1 => self.op_add(instruction),
-            2 => self.op_mult(instruction),
-            3 => self.op_input(instruction),
-            4 => self.op_output(instruction),
+            1 => self.op_add(instruction)?,
+            2 => self.op_mult(instruction)?,
+            3 => self.op_input(instruction)?,
+            4 => self.op_output(instruction)?,
 
-            5 => self.op_jump_if_true(instruction),
-            6 => self.op_jump_if_false(instruction),
-            7 => self.op_less_than(instruction),
-            8 => self.op_equals(instruction),
-            9 => self.op_relative_base(instruction),
+            5 => self.op_jump_if_true(instruction)?,
+            6 => self.op_jump_if_false(instruction)?,
+            7 => self.op_less_than(instruction)?,
+            8 => self.op_equals(instruction)?,
+            9 => self.op_relative_base(instruction)?,
 
             99 => { self.op_exit(); 0 },
-            _ => panic!("Unsupported instruction opcode!"),
+            opcode => return Err(IntcodeError::InvalidOpcode { pc: self.pc, opcode: opcode as i128 }),
         };
 
-        if !self.blocked {
+        if self.state == RunState::Running {
             self.pc = new_pc;
         }
+        Ok(())
     }
 
-    fn op_add(&mut self, instr : &Instruction) -> u128 {
-        let param1 = instr.params.get(0).unwrap();
-        let op1 = self.read_value(param1.address, param1.mode);
-        let param2 = instr.params.get(1).unwrap();
-        let op2 = self.read_value(param2.address, param2.mode);
-        let param3 = instr.params.get(2).unwrap();
+    fn op_add(&mut self, instr : &Instruction) -> Result<u128, IntcodeError> {
+        let op1 = self.operand(instr, 0)?;
+        let op2 = self.operand(instr, 1)?;
+        let param3 = self.param(instr, 2)?;
 
-        let result = op1 + op2;
-        self.write_value(result, param3.address, param3.mode);
+        let Some(result) = self.arithmetic(op1.checked_add(&op2), || op1.wrapping_add(&op2))? else {
+            return Ok(self.pc);
+        };
+        self.write_value(result, param3.address, param3.mode)?;
 
-        self.pc + self.get_increment_for_opcode(&instr.opcode)
+        Ok(self.pc + instr.len())
     }
 
-    fn op_mult(&mut self, instr : &Instruction) -> u128 {
-        let param1 = instr.params.get(0).unwrap();
-        let op1 = self.read_value(param1.address, param1.mode);
-        let param2 = instr.params.get(1).unwrap();
-        let op2 = self.read_value(param2.address, param2.mode);
-        let param3 = instr.params.get(2).unwrap();
+    fn op_mult(&mut self, instr : &Instruction) -> Result<u128, IntcodeError> {
+        let op1 = self.operand(instr, 0)?;
+        let op2 = self.operand(instr, 1)?;
+        let param3 = self.param(instr, 2)?;
 
-        let result = op1 * op2;
-        self.write_value(result, param3.address, param3.mode);
+        let Some(result) = self.arithmetic(op1.checked_mul(&op2), || op1.wrapping_mul(&op2))? else {
+            return Ok(self.pc);
+        };
+        self.write_value(result, param3.address, param3.mode)?;
 
-        self.pc + self.get_increment_for_opcode(&instr.opcode)
+        Ok(self.pc + instr.len())
     }
 
-    fn op_jump_if_true(&mut self, instr : &Instruction) -> u128 {
-        let param1 = instr.params.get(0).unwrap();
-        let op1 = self.read_value(param1.address, param1.mode);
-        let param2 = instr.params.get(1).unwrap();
-        let op2 = self.read_value(param2.address, param2.mode);
+    fn op_jump_if_true(&mut self, instr : &Instruction) -> Result<u128, IntcodeError> {
+        let op1 = self.operand(instr, 0)?;
+        let op2 = self.operand(instr, 1)?;
 
-        if op1 != 0 {
-            op2 as u128
+        if op1 != W::ZERO {
+            self.checked_address(self.to_i128(&op2)?)
         } else {
-            self.pc + self.get_increment_for_opcode(&instr.opcode)
+            Ok(self.pc + instr.len())
         }
     }
 
-    fn op_jump_if_false(&mut self, instr : &Instruction) -> u128 {
-        let param1 = instr.params.get(0).unwrap();
-        let op1 = self.read_value(param1.address, param1.mode);
-        let param2 = instr.params.get(1).unwrap();
-        let op2 = self.read_value(param2.address, param2.mode);
+    fn op_jump_if_false(&mut self, instr : &Instruction) -> Result<u128, IntcodeError> {
+        let op1 = self.operand(instr, 0)?;
+        let op2 = self.operand(instr, 1)?;
 
-        if op1 == 0 {
-            op2 as u128
+        if op1 == W::ZERO {
+            self.checked_address(self.to_i128(&op2)?)
         } else {
-            self.pc + self.get_increment_for_opcode(&instr.opcode)
+            Ok(self.pc + instr.len())
         }
     }
 
-    fn op_less_than(&mut self, instr : &Instruction) -> u128 {
-        let param1 = instr.params.get(0).unwrap();
-        let op1 = self.read_value(param1.address, param1.mode);
-        let param2 = instr.params.get(1).unwrap();
-        let op2 = self.read_value(param2.address, param2.mode);
-        let param3 = instr.params.get(2).unwrap();
+    fn op_less_than(&mut self, instr : &Instruction) -> Result<u128, IntcodeError> {
+        let op1 = self.operand(instr, 0)?;
+        let op2 = self.operand(instr, 1)?;
+        let param3 = self.param(instr, 2)?;
 
         self.write_value(match op1 < op2 {
-            true => 1,
-            false => 0,
-        }, param3.address, param3.mode);
+            true => W::from(1),
+            false => W::ZERO,
+        }, param3.address, param3.mode)?;
 
-        self.pc + self.get_increment_for_opcode(&instr.opcode)
+        Ok(self.pc + instr.len())
     }
 
-    fn op_equals(&mut self, instr : &Instruction) -> u128 {
-        let param1 = instr.params.get(0).unwrap();
-        let op1 = self.read_value(param1.address, param1.mode);
-        let param2 = instr.params.get(1).unwrap();
-        let op2 = self.read_value(param2.address, param2.mode);
-        let param3 = instr.params.get(2).unwrap();
+    fn op_equals(&mut self, instr : &Instruction) -> Result<u128, IntcodeError> {
+        let op1 = self.operand(instr, 0)?;
+        let op2 = self.operand(instr, 1)?;
+        let param3 = self.param(instr, 2)?;
 
         self.write_value(match op1 == op2 {
-            true => 1,
-            false => 0,
-        }, param3.address, param3.mode);
+            true => W::from(1),
+            false => W::ZERO,
+        }, param3.address, param3.mode)?;
 
-        self.pc + self.get_increment_for_opcode(&instr.opcode)
+        Ok(self.pc + instr.len())
     }
 
-    fn op_input(&mut self, instr : &Instruction) -> u128 {
-        let pc_increment = if self.has_input() {
-            let param1 = instr.params.get(0).unwrap();
-            let input = self.read_input();
-            self.write_value(input, param1.address, param1.mode);
-            self.get_increment_for_opcode(&instr.opcode)
+    fn op_input(&mut self, instr : &Instruction) -> Result<u128, IntcodeError> {
+        let param1 = self.param(instr, 0)?;
+        let pc_increment = if let Some(input) = self.read_input()? {
+            self.write_value(input, param1.address, param1.mode)?;
+            instr.len()
         } else {
-            self.blocked = true;
+            self.state = RunState::AwaitingInput;
             0
         };
 
-        self.pc + pc_increment
+        Ok(self.pc + pc_increment)
     }
 
-    fn op_output(&mut self, instr : &Instruction) -> u128 {
-        let param1 = instr.params.get(0).unwrap();
-        let op1 = self.read_value(param1.address as u128, param1.mode);
-        self.output.push_back(op1);
+    fn op_output(&mut self, instr : &Instruction) -> Result<u128, IntcodeError> {
+        let op1 = self.operand(instr, 0)?;
+        self.write_output(op1)?;
 
-        self.pc + self.get_increment_for_opcode(&instr.opcode)
+        Ok(self.pc + instr.len())
     }
 
-    fn op_relative_base(&mut self, instr : &Instruction) -> u128 {
-        let param1 = instr.params.get(0).unwrap();
-        let op1 = self.read_value(param1.address as u128, param1.mode);
-        self.relative_base += op1;
+    fn op_relative_base(&mut self, instr : &Instruction) -> Result<u128, IntcodeError> {
+        let op1 = self.operand(instr, 0)?;
+        self.relative_base = self.relative_base.checked_add(self.to_i128(&op1)?)
+            .ok_or(IntcodeError::RelativeBaseOverflow { pc: self.pc })?;
 
-        self.pc + self.get_increment_for_opcode(&instr.opcode)
+        Ok(self.pc + instr.len())
+    }
+
+    /// Applies the overflow policy to the result of `ADD` or `MUL`. `None`
+    /// means the machine stopped in `RunState::Overflowed` instead.
+    fn arithmetic(&mut self, checked: Option<W>, wrapping: impl FnOnce() -> W) -> Result<Option<W>, IntcodeError> {
+        match (checked, self.overflow) {
+            (Some(result), _) => Ok(Some(result)),
+            (None, Overflow::Wrap) => Ok(Some(wrapping())),
+            (None, Overflow::Promote) => {
+                self.state = RunState::Overflowed;
+                Ok(None)
+            }
+            (None, _) => Err(IntcodeError::Overflow { pc: self.pc }),
+        }
     }
 
     fn op_exit(&mut self) {
-        self.halted = true;
+        self.state = RunState::Halted;
+    }
+}
+
+impl<W: FixedWord> Automaton<W> {
+    /// Continues this machine on `BigInt` words, e.g. after it stopped in
+    /// `RunState::Overflowed`. The overflowing instruction runs again.
+    pub fn promote(self) -> Automaton<BigInt> {
+        let promote_memory = |words: &dyn Memory<W>| {
+            let mut memory = DenseMemory::default();
+            copy_memory(words, &mut memory, |value| BigInt::from(value.into()));
+            memory
+        };
+
+        Automaton {
+            instruction_set: self.instruction_set,
+            pc: self.pc,
+            state: match self.state {
+                RunState::Overflowed => RunState::Running,
+                state => state,
+            },
+            fault: self.fault,
+            memory: Box::new(promote_memory(self.memory.as_ref())),
+            image: Box::new(promote_memory(self.image.as_ref())),
+            decoded: self.decoded,
+            input: self.input.into_iter().map(|value| BigInt::from(value.into())).collect(),
+            output: self.output.into_iter().map(|value| BigInt::from(value.into())).collect(),
+            input_source: self.input_source,
+            output_sink: self.output_sink,
+            output_curr_index: self.output_curr_index,
+            relative_base: self.relative_base,
+            breakpoints: self.breakpoints,
+            watchpoints: self.watchpoints,
+            watch_hit: self.watch_hit,
+            trace: self.trace,
+            instructions: self.instructions,
+            memory_limit: self.memory_limit,
+            protected: self.protected,
+            overflow: self.overflow,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::intcode::Automaton;
+    use std::time::Duration;
+    use crate::intcode::{Automaton, IntcodeError, RunState, DUMP_LIMIT};
+    use crate::intcode::memory::SparseMemory;
+    use crate::intcode::word::{BigInt, Overflow};
 
     #[test]
     fn test_one() {
         let mut automaton = Automaton::new_with_program("1,0,0,0,99")
             .add_initial_input(1);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.dump_memory(), vec![2,0,0,0,99]);
     }
 
@@ -393,7 +887,7 @@ mod tests {
     fn test_two() {
         let mut automaton = Automaton::new_with_program("2,3,0,3,99")
             .add_initial_input(1);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(*automaton.dump_memory(), vec![2,3,0,6,99]);
     }
 
@@ -401,7 +895,7 @@ mod tests {
     fn test_three() {
         let mut automaton = Automaton::new_with_program("2,4,4,5,99,0")
             .add_initial_input(1);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(*automaton.dump_memory(), vec![2,4,4,5,99,9801]);
     }
 
@@ -409,7 +903,7 @@ mod tests {
     fn test_four() {
         let mut automaton = Automaton::new_with_program("1,1,1,4,99,5,6,0,99")
             .add_initial_input(1);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(*automaton.dump_memory(), vec![30,1,1,4,2,5,6,0,99]);
     }
 
@@ -417,7 +911,7 @@ mod tests {
     fn test_five() {
         let mut automaton = Automaton::new_with_program("1002,4,3,4,33")
             .add_initial_input(1);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(*automaton.dump_memory(), vec![1002,4,3,4,99]);
     }
 
@@ -425,7 +919,7 @@ mod tests {
     fn test_negative_values() {
         let mut automaton = Automaton::new_with_program("1101,100,-1,4,0")
             .add_initial_input(1);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(*automaton.dump_memory(), vec![1101,100,-1,4,99]);
     }
 
@@ -433,12 +927,12 @@ mod tests {
     fn test_equal_positional() {
         let mut automaton_equal_8 = Automaton::new_with_program("3,9,8,9,10,9,4,9,99,-1,8")
             .add_initial_input(8);
-        automaton_equal_8.run();
+        automaton_equal_8.run().unwrap();
         assert_eq!(automaton_equal_8.get_last_output(), 1);
 
         let mut automaton_less_than_8 = Automaton::new_with_program("3,9,8,9,10,9,4,9,99,-1,8")
             .add_initial_input(1);
-        automaton_less_than_8.run();
+        automaton_less_than_8.run().unwrap();
         assert_eq!(automaton_less_than_8.get_last_output(), 0);
     }
 
@@ -446,12 +940,12 @@ mod tests {
     fn test_less_than_positional() {
         let mut automaton = Automaton::new_with_program("3,9,7,9,10,9,4,9,99,-1,8")
             .add_initial_input(7);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 1);
 
         let mut automaton = Automaton::new_with_program("3,9,7,9,10,9,4,9,99,-1,8")
             .add_initial_input(9);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 0);
     }
 
@@ -459,12 +953,12 @@ mod tests {
     fn test_equal_immediate() {
         let mut automaton_equal_8 = Automaton::new_with_program("3,3,1108,-1,8,3,4,3,99")
             .add_initial_input(8);
-        automaton_equal_8.run();
+        automaton_equal_8.run().unwrap();
         assert_eq!(automaton_equal_8.get_last_output(), 1);
 
         let mut automaton_less_than_8 = Automaton::new_with_program("3,3,1108,-1,8,3,4,3,99")
             .add_initial_input(1);
-        automaton_less_than_8.run();
+        automaton_less_than_8.run().unwrap();
         assert_eq!(automaton_less_than_8.get_last_output(), 0);
     }
 
@@ -472,12 +966,12 @@ mod tests {
     fn test_less_than_immediate() {
         let mut automaton = Automaton::new_with_program("3,3,1107,-1,8,3,4,3,99")
             .add_initial_input(7);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 1);
 
         let mut automaton = Automaton::new_with_program("3,3,1107,-1,8,3,4,3,99")
             .add_initial_input(9);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 0);
     }
 
@@ -485,12 +979,12 @@ mod tests {
     fn test_jump_position() {
         let mut automaton = Automaton::new_with_program("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9")
             .add_initial_input(0);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 0);
 
         let mut automaton = Automaton::new_with_program("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9")
             .add_initial_input(1);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 1);
     }
 
@@ -498,12 +992,12 @@ mod tests {
     fn test_jump_immediate() {
         let mut automaton = Automaton::new_with_program("3,3,1105,-1,9,1101,0,0,12,4,12,99,1")
             .add_initial_input(0);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 0);
 
         let mut automaton = Automaton::new_with_program("3,3,1105,-1,9,1101,0,0,12,4,12,99,1")
             .add_initial_input(1);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 1);
     }
 
@@ -512,17 +1006,17 @@ mod tests {
         let program = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
         let mut automaton = Automaton::new_with_program(program)
             .add_initial_input(7);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 999);
 
         let mut automaton = Automaton::new_with_program(program)
             .add_initial_input(8);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 1000);
 
         let mut automaton = Automaton::new_with_program(program)
             .add_initial_input(9);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 1001);
     }
 
@@ -530,31 +1024,51 @@ mod tests {
     fn test_relative_example_1() {
         let program = "109,2000,109,19,99";
         let mut automaton = Automaton::new_with_program(program);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.relative_base, 2019);
     }
 
+    #[test]
+    fn test_relative_base_overflow() {
+        let program = format!("109,{},109,1,99", i128::MAX);
+        let mut automaton = Automaton::new_with_program(&program);
+        assert_eq!(automaton.run(), Err(IntcodeError::RelativeBaseOverflow { pc: 2 }));
+
+        let program = format!("109,{},204,1,99", i128::MAX);
+        let mut automaton = Automaton::new_with_program(&program);
+        assert_eq!(automaton.run(), Err(IntcodeError::RelativeBaseOverflow { pc: 2 }));
+    }
+
     #[test]
     fn test_relative_example_2() {
         let program = "109,2019,204,-34,99";
         let mut automaton = Automaton::new_with_program(program);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 0);
     }
 
     #[test]
     fn test_produce_copy_of_self() {
         let program = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let mut expected = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+        expected.resize(100, 0);
+        expected.extend([16, 1]);
+
         let mut automaton = Automaton::new_with_program(program);
-        automaton.run();
-        assert_eq!(automaton.dump_memory(), vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99, 16, 1]);
+        automaton.run().unwrap();
+        assert_eq!(automaton.dump_memory(), expected);
+
+        let mut automaton = Automaton::new_with_program(program).with_memory(SparseMemory::new());
+        automaton.run().unwrap();
+        assert_eq!(automaton.dump_memory(), expected);
+        assert_eq!(automaton.get_output(16), Some(program.split(',').map(|w| w.parse().unwrap()).collect()));
     }
 
     #[test]
     fn test_produce_16_digit_output() {
         let program = "1102,34915192,34915192,7,4,7,99,0";
         let mut automaton = Automaton::new_with_program(program);
-        automaton.run();
+        automaton.run().unwrap();
         assert!(automaton.get_last_output() > 1_000_000_000_000_000);
     }
 
@@ -562,7 +1076,302 @@ mod tests {
     fn test_output_large_nr() {
         let program = "104,1125899906842624,99";
         let mut automaton = Automaton::new_with_program(program);
-        automaton.run();
+        automaton.run().unwrap();
         assert_eq!(automaton.get_last_output(), 1125899906842624);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_invalid_opcode() {
+        let mut automaton = Automaton::new_with_program("1,0,0,0,42");
+        assert_eq!(automaton.run(), Err(IntcodeError::InvalidOpcode { pc: 4, opcode: 42 }));
+    }
+
+    #[test]
+    fn test_invalid_parameter_mode() {
+        let mut automaton = Automaton::new_with_program("301,0,0,0,99");
+        assert_eq!(automaton.run(), Err(IntcodeError::InvalidParameterMode { pc: 0, mode: 3 }));
+    }
+
+    #[test]
+    fn test_write_to_immediate() {
+        let mut automaton = Automaton::new_with_program("11101,1,1,0,99");
+        assert_eq!(automaton.run(), Err(IntcodeError::WriteToImmediate { pc: 0 }));
+    }
+
+    #[test]
+    fn test_negative_address() {
+        let mut automaton = Automaton::new_with_program("1,-1,0,0,99");
+        assert_eq!(automaton.run(), Err(IntcodeError::NegativeAddress { pc: 0, address: -1 }));
+
+        let mut automaton = Automaton::new_with_program("109,-5,204,0,99");
+        assert_eq!(automaton.run(), Err(IntcodeError::NegativeAddress { pc: 2, address: -5 }));
+
+        let mut automaton = Automaton::new_with_program("1105,1,-3");
+        assert_eq!(automaton.run(), Err(IntcodeError::NegativeAddress { pc: 0, address: -3 }));
+    }
+
+    #[test]
+    fn test_run_state_transitions() {
+        let mut automaton = Automaton::new_with_program("3,9,4,9,3,9,4,9,99,0");
+        assert_eq!(automaton.state(), RunState::Running);
+        assert_eq!(automaton.run(), Ok(RunState::AwaitingInput));
+        assert_eq!(automaton.run(), Ok(RunState::AwaitingInput));
+
+        automaton.runtime_input(5).unwrap();
+        assert_eq!(automaton.run(), Ok(RunState::AwaitingInput));
+        assert_eq!(automaton.get_last_output(), 5);
+
+        automaton.runtime_input(7).unwrap();
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.get_last_output(), 7);
+        assert!(automaton.is_halted());
+
+        assert_eq!(automaton.runtime_input(1), Err(IntcodeError::IllegalTransition { pc: 8, state: RunState::Halted }));
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+    }
+
+    #[test]
+    fn test_step() {
+        let mut automaton = Automaton::new_with_program("1101,1,2,5,99,0");
+        assert_eq!(automaton.step(), Ok(RunState::Running));
+        assert_eq!(automaton.pc, 4);
+        assert_eq!(automaton.step(), Ok(RunState::Halted));
+        assert_eq!(automaton.dump_memory(), vec![1101,1,2,5,99,3]);
+    }
+
+    #[test]
+    fn test_faulted_machine_stays_faulted() {
+        let mut automaton = Automaton::new_with_program("42");
+        let error = IntcodeError::InvalidOpcode { pc: 0, opcode: 42 };
+        assert_eq!(automaton.run(), Err(error.clone()));
+        assert_eq!(automaton.state(), RunState::Faulted);
+        assert_eq!(automaton.run(), Err(error));
+        assert!(automaton.runtime_input(1).is_err());
+    }
+
+    #[test]
+    fn test_self_modifying_code_after_decode() {
+        // outputs #20, patches that instruction to position mode and jumps back once
+        let program = "104,20,1001,30,1,30,1101,0,4,0,1008,30,2,31,1005,31,20,1106,0,0,99";
+        let mut automaton = Automaton::new_with_program(program);
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.get_output(2), Some(vec![20, 99]));
+    }
+
+    #[test]
+    fn test_far_pc_is_not_cached() {
+        // 2^64 must not be truncated to the cached instruction at 0
+        let mut automaton = Automaton::new_with_program("1105,1,18446744073709551616");
+        assert_eq!(automaton.run_for(1000), Err(IntcodeError::InvalidOpcode { pc: 1 << 64, opcode: 0 }));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut automaton = Automaton::new_with_program("104,1,104,2,104,3,99");
+        automaton.add_breakpoint(2);
+        automaton.add_breakpoint(6);
+        assert_eq!(automaton.run(), Ok(RunState::Breakpoint));
+        assert_eq!(automaton.pc, 2);
+        assert_eq!(automaton.get_output(1), Some(vec![1]));
+
+        assert!(automaton.remove_breakpoint(6));
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.get_output(2), Some(vec![2, 3]));
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut automaton = Automaton::new_with_program("1101,1,2,9,1101,3,4,10,99,0,0");
+        automaton.add_watchpoint(10);
+        assert_eq!(automaton.run(), Ok(RunState::Breakpoint));
+        assert_eq!(automaton.watch_hit(), Some(10));
+        assert_eq!(automaton.pc, 8);
+        assert_eq!(automaton.dump_memory()[9..], [3, 7]);
+
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.watch_hit(), None);
+    }
+
+    #[test]
+    fn test_fork() {
+        // keeps adding its inputs to address 13 and outputs the total
+        let program = "3,12,1,12,13,13,4,13,1105,1,0,99,0,0";
+        let mut automaton = Automaton::new_with_program(program).add_initial_input(5);
+        automaton.run().unwrap();
+
+        let mut fork = automaton.fork();
+        automaton.runtime_input(1).unwrap();
+        fork.runtime_input(100).unwrap();
+        automaton.run().unwrap();
+        fork.run().unwrap();
+
+        assert_eq!(automaton.output, vec![5, 6]);
+        assert_eq!(fork.output, vec![5, 105]);
+        assert_eq!(automaton.dump_memory()[13], 6);
+        assert_eq!(fork.clone().dump_memory()[13], 105);
+    }
+
+    #[test]
+    fn test_run_for() {
+        // counts down from 5 in address 9, then halts
+        let mut automaton = Automaton::new_with_program("1001,9,-1,9,1005,9,0,99,0,5");
+        assert_eq!(automaton.run_for(3), Ok(RunState::StepLimitReached));
+        assert_eq!(automaton.instructions_executed(), 3);
+        assert_eq!(automaton.pc, 4);
+
+        assert_eq!(automaton.run_for(100), Ok(RunState::Halted));
+        assert_eq!(automaton.instructions_executed(), 11);
+        assert_eq!(automaton.memory().read(9), 0);
+    }
+
+    #[test]
+    fn test_run_with_timeout() {
+        let mut automaton = Automaton::new_with_program("1105,1,0");
+        assert_eq!(automaton.run_with_timeout(Duration::from_millis(20)), Ok(RunState::StepLimitReached));
+        let executed = automaton.instructions_executed();
+        assert!(executed > 0);
+
+        assert_eq!(automaton.run_for(10), Ok(RunState::StepLimitReached));
+        assert_eq!(automaton.instructions_executed(), executed + 10);
+    }
+
+    #[test]
+    fn test_switch_backend_keeps_trailing_zeros() {
+        let program = "1101,2,3,5,99,0,0,0";
+        let dense = Automaton::new_with_program(program);
+        let sparse = Automaton::new_with_program(program).with_memory(SparseMemory::new());
+        assert_eq!(sparse.memory().len(), 8);
+        assert_eq!(sparse.dump_memory(), dense.dump_memory());
+    }
+
+    #[test]
+    fn test_dump_far_memory() {
+        let mut automaton = Automaton::new_with_program("1101,1,1,1099511627776,99");
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.dump_memory().len() as u128, DUMP_LIMIT);
+        assert_eq!(automaton.dump_range((1 << 40) - 1..(1 << 40) + 1), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let mut automaton = Automaton::new_with_program("1101,1,2,9,1001,9,5,20,99,0").with_memory_limit(16);
+        assert_eq!(automaton.run(), Err(IntcodeError::AddressOutOfRange { pc: 4, address: 20, limit: 16 }));
+        assert_eq!(automaton.memory().read(9), 3);
+
+        // the jump itself faults, not the fetch at its target
+        let mut automaton = Automaton::new_with_program("1105,1,40").with_memory_limit(16);
+        assert_eq!(automaton.run(), Err(IntcodeError::AddressOutOfRange { pc: 0, address: 40, limit: 16 }));
+    }
+
+    #[test]
+    fn test_protected_program() {
+        let mut automaton = Automaton::new_with_program("1101,1,2,7,4,7,99,0").with_protected_program();
+        assert_eq!(automaton.run(), Err(IntcodeError::WriteToProtected { pc: 0, address: 7 }));
+
+        let mut automaton = Automaton::new_with_program("1101,1,2,9,4,9,99").with_protected_program();
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.get_last_output(), 3);
+    }
+
+    // squares its input twice and outputs the result
+    const SQUARE_TWICE: &str = "3,13,2,13,13,13,2,13,13,13,4,13,99";
+
+    #[test]
+    fn test_i64_words() {
+        let mut automaton = Automaton::<i64>::new_with_words(SQUARE_TWICE).add_initial_input(-7);
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.get_last_output(), 2401);
+    }
+
+    #[test]
+    fn test_overflow_policies() {
+        let big = 1 << 20;
+        let mut automaton = Automaton::<i64>::new_with_words(SQUARE_TWICE).add_initial_input(big);
+        assert_eq!(automaton.run(), Err(IntcodeError::Overflow { pc: 6 }));
+
+        let mut automaton = Automaton::<i64>::new_with_words(SQUARE_TWICE)
+            .with_overflow(Overflow::Wrap)
+            .add_initial_input(big);
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.get_last_output(), 0);
+    }
+
+    #[test]
+    fn test_promote() {
+        let mut automaton = Automaton::<i64>::new_with_words(SQUARE_TWICE)
+            .with_overflow(Overflow::Promote)
+            .add_initial_input(1 << 20);
+        assert_eq!(automaton.run(), Ok(RunState::Overflowed));
+        assert_eq!(automaton.run(), Ok(RunState::Overflowed));
+        assert_eq!(automaton.pc, 6);
+        assert_eq!(automaton.instructions_executed(), 2);
+
+        let mut automaton = automaton.promote();
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.get_last_output(), BigInt::from(1) << 80);
+    }
+
+    #[test]
+    fn test_promote_default_words() {
+        let mut automaton = Automaton::new_with_program(SQUARE_TWICE)
+            .with_overflow(Overflow::Promote)
+            .add_initial_input(1 << 40);
+        assert_eq!(automaton.run(), Ok(RunState::Overflowed));
+        assert_eq!(automaton.pc, 6);
+
+        let mut automaton = automaton.promote();
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.get_last_output(), BigInt::from(1) << 160);
+        assert_eq!(automaton.peek(13), BigInt::from(1) << 160);
+    }
+
+    #[test]
+    fn test_big_words() {
+        let mut automaton = Automaton::<BigInt>::new_with_words(SQUARE_TWICE).add_initial_input(BigInt::from(i128::MAX));
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.get_last_output(), BigInt::from(i128::MAX).pow(4));
+
+        // too large to be an address
+        let mut automaton = Automaton::<BigInt>::new_with_words("4,340282366920938463463374607431768211456,99");
+        assert_eq!(automaton.run(), Err(IntcodeError::WordOutOfRange { pc: 0 }));
+    }
+
+    #[test]
+    fn test_peek_and_poke() {
+        let mut automaton = Automaton::new_with_program("1,0,0,0,99");
+        automaton.poke(2, 4);
+        assert_eq!(automaton.peek(2), 4);
+        automaton.run().unwrap();
+        assert_eq!(automaton.dump_memory(), vec![100,0,4,0,99]);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut automaton = Automaton::new_with_program("1,0,0,0,99").with_patch(1, 4);
+        automaton.run().unwrap();
+        assert_eq!(automaton.peek(0), 100);
+
+        // pokes last until the next reset, patches survive it
+        automaton.reset();
+        automaton.poke(2, 2);
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.dump_memory(), vec![101,4,2,0,99]);
+        automaton.reset();
+        assert_eq!(automaton.dump_memory(), vec![1,4,0,0,99]);
+        assert_eq!(automaton.instructions_executed(), 0);
+    }
+
+    #[test]
+    fn test_reset_after_self_modification() {
+        // the input becomes the next instruction
+        let mut automaton = Automaton::new_with_program("3,2,0,0,99");
+        automaton.runtime_input(104).unwrap();
+        automaton.run().unwrap();
+        assert_eq!(automaton.get_output(1), Some(vec![0]));
+
+        automaton.reset();
+        automaton.runtime_input(99).unwrap();
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert!(!automaton.has_output());
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use crate::intcode::{Automaton, ParameterMode};
+use crate::intcode::disasm::{disassemble_at, Line, Operand};
+use crate::intcode::word::FixedWord;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction.
+    FallThrough,
+    /// A `JT` or `JF` jumps to its immediate target.
+    Jump,
+}
+
+/// A run of instructions that is only entered at its first instruction and
+/// only left after its last one.
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub lines: Vec<Line>,
+    /// Start addresses of the blocks execution can continue with.
+    pub successors: Vec<(u128, EdgeKind)>,
+    /// Whether the block ends in a jump whose target is only known at run time.
+    pub indirect: bool,
+}
+
+impl BasicBlock {
+    pub fn start(&self) -> u128 {
+        self.lines[0].address()
+    }
+
+    /// The address after the last word of the block.
+    pub fn end(&self) -> u128 {
+        self.lines.last().map_or(self.start(), |line| line.address() + line.size())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RegionKind {
+    Code,
+    Data,
+}
+
+/// The addresses `start..end`, which are all reachable code or all data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub start: u128,
+    pub end: u128,
+    pub kind: RegionKind,
+}
+
+/// Something about reachable code that static analysis cannot see through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// A `JT` or `JF` at `address` whose target is read from memory.
+    IndirectJump { address: u128 },
+    /// The instruction at `address` writes to `target`, which is part of the
+    /// reachable code. Writes relative to the relative base are not checked.
+    SelfModifyingWrite { address: u128, target: u128 },
+    /// Execution reaches `address`, which holds no valid instruction.
+    InvalidInstruction { address: u128 },
+}
+
+impl Finding {
+    pub fn address(&self) -> u128 {
+        match self {
+            Finding::IndirectJump { address }
+            | Finding::SelfModifyingWrite { address, .. }
+            | Finding::InvalidInstruction { address } => *address,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::IndirectJump { address } => write!(f, "{address}: indirect jump"),
+            Finding::SelfModifyingWrite { address, target } => write!(f, "{address}: writes to code at {target}"),
+            Finding::InvalidInstruction { address } => write!(f, "{address}: reachable invalid instruction"),
+        }
+    }
+}
+
+/// The control-flow graph of a program, recovered without running it.
+#[derive(Debug, Clone)]
+pub struct ControlFlowGraph {
+    /// Blocks by start address; the entry block starts at 0.
+    pub blocks: BTreeMap<u128, BasicBlock>,
+    /// Sorted by address.
+    pub findings: Vec<Finding>,
+    len: u128,
+}
+
+/// Where execution can go after one line.
+struct Flow {
+    successors: Vec<(u128, EdgeKind)>,
+    ends_block: bool,
+    indirect: bool,
+}
+
+fn flow(line: &Line) -> Flow {
+    let next = line.address() + line.size();
+    let (mnemonic, operands) = match line {
+        Line::Instruction { mnemonic, operands, .. } => (*mnemonic, operands),
+        Line::Data { .. } => return Flow { successors: Vec::new(), ends_block: true, indirect: false },
+    };
+    let jump_if = match mnemonic {
+        "HLT" => return Flow { successors: Vec::new(), ends_block: true, indirect: false },
+        "JT" => true,
+        "JF" => false,
+        _ => return Flow { successors: vec![(next, EdgeKind::FallThrough)], ends_block: false, indirect: false },
+    };
+
+    // an immediate condition decides the jump, as in `JT #1, #loop`
+    let (taken, falls_through) = match operands[0] {
+        Operand { mode: ParameterMode::Immediate, value } => ((value != 0) == jump_if, (value != 0) != jump_if),
+        _ => (true, true),
+    };
+    let target = match operands[1] {
+        Operand { mode: ParameterMode::Immediate, value } => u128::try_from(value).ok(),
+        _ => None,
+    };
+    let mut successors = Vec::new();
+    if let (true, Some(target)) = (taken, target) {
+        successors.push((target, EdgeKind::Jump));
+    }
+    if falls_through {
+        successors.push((next, EdgeKind::FallThrough));
+    }
+    Flow { successors, ends_block: true, indirect: taken && target.is_none() }
+}
+
+/// Follows every path from address 0, through the immediate targets of `JT`
+/// and `JF`, to find the reachable instructions and split them into blocks.
+pub fn analyze<W: FixedWord>(automaton: &Automaton<W>) -> ControlFlowGraph {
+    let mut lines = BTreeMap::new();
+    let mut leaders = BTreeSet::from([0]);
+    let mut pending = vec![0];
+    while let Some(address) = pending.pop() {
+        if lines.contains_key(&address) {
+            continue;
+        }
+        let line = disassemble_at(automaton, address);
+        let flow = flow(&line);
+        for &(successor, _) in &flow.successors {
+            if flow.ends_block {
+                leaders.insert(successor);
+            }
+            pending.push(successor);
+        }
+        lines.insert(address, line);
+    }
+
+    let mut blocks = BTreeMap::new();
+    for &leader in &leaders {
+        let mut block = BasicBlock { lines: Vec::new(), successors: Vec::new(), indirect: false };
+        let mut address = leader;
+        loop {
+            let line = lines[&address].clone();
+            let flow = flow(&line);
+            address += line.size();
+            block.lines.push(line);
+            if flow.ends_block {
+                block.successors = flow.successors;
+                block.indirect = flow.indirect;
+                break;
+            }
+            if leaders.contains(&address) {
+                block.successors = vec![(address, EdgeKind::FallThrough)];
+                break;
+            }
+        }
+        blocks.insert(leader, block);
+    }
+
+    let mut graph = ControlFlowGraph { blocks, findings: Vec::new(), len: automaton.memory().len() };
+    graph.findings = graph.find_issues();
+    graph
+}
+
+impl ControlFlowGraph {
+    /// Every reachable line, including the invalid words execution runs into.
+    fn lines(&self) -> impl Iterator<Item = &Line> {
+        self.blocks.values().flat_map(|block| &block.lines)
+    }
+
+    fn find_issues(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for block in self.blocks.values() {
+            if block.indirect {
+                findings.push(Finding::IndirectJump { address: block.lines[block.lines.len() - 1].address() });
+            }
+            for line in &block.lines {
+                match line {
+                    Line::Data { address, .. } => findings.push(Finding::InvalidInstruction { address: *address }),
+                    Line::Instruction { address, operands, writes: true, .. } => {
+                        if let Some(&Operand { mode: ParameterMode::Position, value }) = operands.last() {
+                            if let Ok(target) = u128::try_from(value) {
+                                if self.is_code(target) {
+                                    findings.push(Finding::SelfModifyingWrite { address: *address, target });
+                                }
+                            }
+                        }
+                    }
+                    Line::Instruction { .. } => {}
+                }
+            }
+        }
+        findings.sort_by_key(Finding::address);
+        findings
+    }
+
+    /// Whether execution can reach `address` as part of an instruction,
+    /// valid or not.
+    pub fn is_code(&self, address: u128) -> bool {
+        self.lines().any(|line| (line.address()..line.address() + line.size()).contains(&address))
+    }
+
+    /// Splits the loaded memory into reachable code and everything else.
+    pub fn regions(&self) -> Vec<Region> {
+        let mut code : Vec<(u128, u128)> = self.lines()
+            .map(|line| (line.address(), line.address() + line.size()))
+            .collect();
+        code.sort_unstable();
+
+        let mut regions : Vec<Region> = Vec::new();
+        let mut push = |start: u128, end: u128, kind: RegionKind| {
+            if start >= end {
+                return;
+            }
+            match regions.last_mut() {
+                Some(last) if last.kind == kind && last.end >= start => last.end = last.end.max(end),
+                _ => regions.push(Region { start, end, kind }),
+            }
+        };
+        let mut covered = 0;
+        for (start, end) in code {
+            push(covered, start, RegionKind::Data);
+            push(start, end, RegionKind::Code);
+            covered = covered.max(end);
+        }
+        push(covered, self.len, RegionKind::Data);
+        regions
+    }
+
+    /// The graph in Graphviz DOT, e.g. for `dot -Tsvg`. Indirect jumps lead
+    /// to a dashed edge into a `?` node.
+    pub fn to_dot(&self) -> String {
+        let escape = |text: String| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for (start, block) in &self.blocks {
+            let label : String = block.lines.iter()
+                .map(|line| format!("{}: {}\\l", line.address(), escape(line.source())))
+                .collect();
+            dot += &format!("    b{start} [label=\"{label}\"];\n");
+        }
+        for (start, block) in &self.blocks {
+            for (successor, kind) in &block.successors {
+                match kind {
+                    EdgeKind::FallThrough => dot += &format!("    b{start} -> b{successor};\n"),
+                    EdgeKind::Jump => dot += &format!("    b{start} -> b{successor} [label=\"jump\"];\n"),
+                }
+            }
+            if block.indirect {
+                dot += &format!("    b{start} -> indirect [style=dashed];\n");
+            }
+        }
+        if self.blocks.values().any(|block| block.indirect) {
+            dot += "    indirect [label=\"?\", shape=circle];\n";
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::Automaton;
+    use crate::intcode::analysis::{analyze, EdgeKind, Finding, Region, RegionKind};
+
+    // doubles every input until it reads a 0
+    const DOUBLER: &str = "3,20,1006,20,14,102,2,20,21,4,21,1105,1,0,99";
+
+    #[test]
+    fn test_blocks_and_edges() {
+        let graph = analyze(&Automaton::new_with_program(DOUBLER));
+        let spans : Vec<(u128, u128)> = graph.blocks.values().map(|block| (block.start(), block.end())).collect();
+        assert_eq!(spans, vec![(0, 5), (5, 14), (14, 15)]);
+        assert_eq!(graph.blocks[&0].successors, vec![(14, EdgeKind::Jump), (5, EdgeKind::FallThrough)]);
+        assert_eq!(graph.blocks[&5].successors, vec![(0, EdgeKind::Jump)]);
+        assert_eq!(graph.blocks[&14].successors, vec![]);
+        assert!(graph.findings.is_empty());
+    }
+
+    #[test]
+    fn test_regions() {
+        // jumps over a data word and falls into an invalid one
+        let graph = analyze(&Automaton::new_with_program("1105,1,4,99,1101,2,3,20,42,7"));
+        assert_eq!(graph.regions(), vec![
+            Region { start: 0, end: 3, kind: RegionKind::Code },
+            Region { start: 3, end: 4, kind: RegionKind::Data },
+            Region { start: 4, end: 9, kind: RegionKind::Code },
+            Region { start: 9, end: 10, kind: RegionKind::Data },
+        ]);
+        assert_eq!(graph.findings, vec![Finding::InvalidInstruction { address: 8 }]);
+    }
+
+    #[test]
+    fn test_indirect_jump_and_self_modification() {
+        let graph = analyze(&Automaton::new_with_program("3,20,6,20,21,1101,1,1,9,99"));
+        assert_eq!(graph.findings, vec![
+            Finding::IndirectJump { address: 2 },
+            Finding::SelfModifyingWrite { address: 5, target: 9 },
+        ]);
+
+        // overwrites the invalid word it then runs into
+        let graph = analyze(&Automaton::new_with_program("1101,99,0,4,0"));
+        assert_eq!(graph.findings, vec![
+            Finding::SelfModifyingWrite { address: 0, target: 4 },
+            Finding::InvalidInstruction { address: 4 },
+        ]);
+    }
+
+    #[test]
+    fn test_dot() {
+        let dot = analyze(&Automaton::new_with_program(DOUBLER)).to_dot();
+        assert!(dot.starts_with("digraph cfg {\n"));
+        assert!(dot.contains("    b0 [label=\"0: IN -> @20\\l2: JF @20, #14\\l\"];\n"));
+        assert!(dot.contains("    b0 -> b14 [label=\"jump\"];\n    b0 -> b5;\n"));
+        assert!(dot.contains("    b5 -> b0 [label=\"jump\"];\n"));
+        assert!(!dot.contains("indirect"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::intcode::{Automaton, IntcodeError};
+use crate::intcode::word::FixedWord;
+
+/// Unread output split into the part that is ASCII text and the values that
+/// are not, such as the final answer of an ASCII-speaking program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AsciiOutput {
+    pub text: String,
+    pub values: Vec<i128>,
+}
+
+fn to_char<W: FixedWord>(value: W) -> Option<char> {
+    u8::try_from(value.into()).ok()
+        .filter(u8::is_ascii)
+        .map(char::from)
+}
+
+impl<W: FixedWord> Automaton<W> {
+    /// Queues `line` as ASCII codes followed by a newline, e.g. at startup with
+    /// `Automaton::new_with_program(program).add_initial_line("NOT A J")`.
+    pub fn add_initial_line(mut self, line: &str) -> Self {
+        self.input.extend(line.bytes().chain(Some(b'\n')).map(W::from));
+
+        self
+    }
+
+    /// Queues `line` as ASCII codes followed by a newline.
+    pub fn runtime_line(&mut self, line: &str) -> Result<(), IntcodeError> {
+        line.bytes()
+            .chain(Some(b'\n'))
+            .try_for_each(|byte| self.runtime_input(W::from(byte)))
+    }
+
+    /// Reads unread output as text up to and including the first occurrence
+    /// of `prompt`. Returns `None`, reading nothing, if the text before the
+    /// next non-ASCII value does not contain the prompt yet.
+    pub fn read_text_until(&mut self, prompt: &str) -> Option<String> {
+        let text : String = self.output.iter()
+            .skip(self.output_curr_index)
+            .map_while(|&value| to_char(value))
+            .collect();
+        let end = text.find(prompt)? + prompt.len();
+        self.output_curr_index += end;
+        Some(text[..end].to_string())
+    }
+
+    /// Reads all unread output, keeping text and other values apart.
+    pub fn read_ascii(&mut self) -> AsciiOutput {
+        let mut ascii = AsciiOutput::default();
+        for &value in self.output.iter().skip(self.output_curr_index) {
+            match to_char(value) {
+                Some(c) => ascii.text.push(c),
+                None => ascii.values.push(value.into()),
+            }
+        }
+        self.output_curr_index = self.output.len();
+        ascii
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::{Automaton, RunState};
+    use crate::intcode::ascii::AsciiOutput;
+    use crate::intcode::asm::assemble;
+
+    // prints a prompt, then echoes the line it reads in upper case and
+    // outputs its length
+    const SHOUT: &str = "
+            out #62
+            out #32
+    loop:   in -> c
+            eq c, #10 -> done
+            jt done, #end
+            lt c, #97 -> done
+            jt done, #keep
+            add c, #-32 -> c
+    keep:   out c
+            add length, #1 -> length
+            jt #1, #loop
+    end:    out #10
+            mul length, #1000 -> length
+            out length
+            hlt
+    c:      data 0
+    done:   data 0
+    length: data 0
+    ";
+
+    #[test]
+    fn test_line_and_prompt() {
+        let mut automaton = Automaton::new_with_program(&assemble(SHOUT).unwrap());
+        assert_eq!(automaton.run(), Ok(RunState::AwaitingInput));
+        assert_eq!(automaton.read_text_until("$ "), None);
+        assert_eq!(automaton.read_text_until("> "), Some(String::from("> ")));
+
+        automaton.runtime_line("hi 2u").unwrap();
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.read_text_until("\n"), Some(String::from("HI 2U\n")));
+        assert_eq!(automaton.read_ascii(), AsciiOutput { text: String::new(), values: vec![5000] });
+    }
+
+    #[test]
+    fn test_text_and_values_are_separated() {
+        let mut automaton = Automaton::new_with_program(&assemble(SHOUT).unwrap()).add_initial_line("ok");
+        automaton.run().unwrap();
+        assert_eq!(automaton.read_ascii(), AsciiOutput { text: String::from("> OK\n"), values: vec![2000] });
+        assert_eq!(automaton.read_ascii(), AsciiOutput::default());
+    }
+
+    #[test]
+    fn test_i64_words() {
+        let mut automaton = Automaton::<i64>::new_with_words(&assemble(SHOUT).unwrap()).add_initial_line("abc");
+        automaton.run().unwrap();
+        assert_eq!(automaton.read_ascii(), AsciiOutput { text: String::from("> ABC\n"), values: vec![3000] });
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::intcode::{Automaton, ParameterMode};
+
+/// Error raised by `assemble`, with the 1-based source line it occurred on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnknownMnemonic(String),
+    WrongOperandCount { expected: usize, found: usize },
+    InvalidOperand(String),
+    ImmediateDestination,
+    DuplicateSymbol(String),
+    UndefinedSymbol(String),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(mnemonic) => write!(f, "unknown mnemonic '{mnemonic}'"),
+            AsmErrorKind::WrongOperandCount { expected, found } => write!(f, "expected {expected} operands, found {found}"),
+            AsmErrorKind::InvalidOperand(operand) => write!(f, "invalid operand '{operand}'"),
+            AsmErrorKind::ImmediateDestination => write!(f, "destination cannot be an immediate value"),
+            AsmErrorKind::DuplicateSymbol(symbol) => write!(f, "symbol '{symbol}' defined twice"),
+            AsmErrorKind::UndefinedSymbol(symbol) => write!(f, "undefined symbol '{symbol}'"),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// A value in the source: a number, a symbol, or a symbol plus an offset.
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i128),
+    Symbol(String, i128),
+}
+
+#[derive(Debug, Clone)]
+enum Item {
+    Instruction { opcode: i8, operands: Vec<(ParameterMode, Expr)> },
+    Data(Vec<Expr>),
+}
+
+/// Assembles source text into the comma-separated format loaded by
+/// `Automaton::new_with_program`.
+///
+/// ```text
+/// ; comments run to the end of the line
+/// size = 3                  ; symbol definition
+/// start:  in -> @buffer     ; labels name the address of the next word
+///         add @buffer, #size -> [r+1]
+///         jt #1, #start
+///         hlt
+/// buffer: data 0, 0, start
+/// ```
+///
+/// Operands are `@x` (position), `#x` (immediate) or `[r+x]` (relative,
+/// `[rb+x]` and `[r-x]` are accepted too); a bare `x` is position mode. The
+/// destination of a writing instruction may be introduced by `->` instead of
+/// a comma. Mnemonics are those of `Automaton::init` and are case-insensitive.
+pub fn assemble(source: &str) -> Result<String, AsmError> {
+    let words = assemble_words(source)?;
+    Ok(words.iter().map(|word| word.to_string()).collect::<Vec<_>>().join(","))
+}
+
+pub fn assemble_words(source: &str) -> Result<Vec<i128>, AsmError> {
+    let instruction_set = &Automaton::new().instruction_set;
+    let mnemonics : HashMap<String, i8> = instruction_set.iter()
+        .map(|(&opcode, def)| (def.mnemonic.to_lowercase(), opcode))
+        .collect();
+
+    let mut symbols : HashMap<String, i128> = HashMap::new();
+    let mut items : Vec<(usize, Item)> = Vec::new();
+    let mut address : i128 = 0;
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_no = index + 1;
+        let error = |kind| AsmError { line: line_no, kind };
+        let mut line = raw_line.split(';').next().unwrap_or_default().trim();
+
+        if let Some((name, value)) = line.split_once('=') {
+            let name = name.trim();
+            let value = match parse_expr(value.trim()) {
+                Some(Expr::Number(value)) if is_symbol(name) => value,
+                _ => return Err(error(AsmErrorKind::InvalidOperand(line.to_string()))),
+            };
+            define(&mut symbols, name, value).map_err(error)?;
+            continue;
+        }
+
+        while let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_symbol(label) {
+                return Err(error(AsmErrorKind::InvalidOperand(label.to_string())));
+            }
+            define(&mut symbols, label, address).map_err(error)?;
+            line = rest.trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.replace("->", ",");
+        let operands : Vec<&str> = rest.split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .collect();
+
+        let item = if mnemonic.eq_ignore_ascii_case("data") {
+            let values = operands.iter()
+                .map(|&operand| parse_expr(operand).ok_or(error(AsmErrorKind::InvalidOperand(operand.to_string()))))
+                .collect::<Result<Vec<_>, _>>()?;
+            address += values.len() as i128;
+            Item::Data(values)
+        } else {
+            let opcode = *mnemonics.get(&mnemonic.to_lowercase())
+                .ok_or(error(AsmErrorKind::UnknownMnemonic(mnemonic.to_string())))?;
+            let def = &instruction_set[&opcode];
+            if operands.len() != def.no_params as usize {
+                return Err(error(AsmErrorKind::WrongOperandCount { expected: def.no_params as usize, found: operands.len() }));
+            }
+            let operands = operands.iter()
+                .map(|&operand| parse_operand(operand).ok_or(error(AsmErrorKind::InvalidOperand(operand.to_string()))))
+                .collect::<Result<Vec<_>, _>>()?;
+            if def.writes && matches!(operands.last(), Some((ParameterMode::Immediate, _))) {
+                return Err(error(AsmErrorKind::ImmediateDestination));
+            }
+            address += operands.len() as i128 + 1;
+            Item::Instruction { opcode, operands }
+        };
+        items.push((line_no, item));
+    }
+
+    let mut words = Vec::new();
+    for (line_no, item) in items {
+        let resolve = |expr: &Expr| match expr {
+            Expr::Number(value) => Ok(*value),
+            Expr::Symbol(name, offset) => symbols.get(name)
+                .map(|value| value + offset)
+                .ok_or(AsmError { line: line_no, kind: AsmErrorKind::UndefinedSymbol(name.clone()) }),
+        };
+        match item {
+            Item::Instruction { opcode, operands } => {
+                let modes = operands.iter().rev()
+                    .fold(0, |modes, (mode, _)| modes * 10 + *mode as i128);
+                words.push(modes * 100 + opcode as i128);
+                for (_, expr) in &operands {
+                    words.push(resolve(expr)?);
+                }
+            }
+            Item::Data(values) => {
+                for expr in &values {
+                    words.push(resolve(expr)?);
+                }
+            }
+        }
+    }
+    Ok(words)
+}
+
+fn define(symbols: &mut HashMap<String, i128>, name: &str, value: i128) -> Result<(), AsmErrorKind> {
+    if symbols.insert(name.to_string(), value).is_some() {
+        Err(AsmErrorKind::DuplicateSymbol(name.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+fn is_symbol(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_operand(text: &str) -> Option<(ParameterMode, Expr)> {
+    if let Some(expr) = text.strip_prefix('#') {
+        Some((ParameterMode::Immediate, parse_expr(expr.trim())?))
+    } else if let Some(expr) = text.strip_prefix('@') {
+        Some((ParameterMode::Position, parse_expr(expr.trim())?))
+    } else if let Some(inner) = text.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+        let inner = inner.trim();
+        let offset = inner.strip_prefix("rb").or_else(|| inner.strip_prefix('r'))?.trim();
+        let expr = if offset.is_empty() {
+            Expr::Number(0)
+        } else if let Some(expr) = offset.strip_prefix('+') {
+            parse_expr(expr.trim())?
+        } else if offset.starts_with('-') {
+            parse_expr(offset)?
+        } else {
+            return None;
+        };
+        Some((ParameterMode::Relative, expr))
+    } else {
+        Some((ParameterMode::Position, parse_expr(text)?))
+    }
+}
+
+/// Parses `42`, `-7`, `label`, `label+3` or `label-1`.
+fn parse_expr(text: &str) -> Option<Expr> {
+    if let Ok(value) = text.parse() {
+        return Some(Expr::Number(value));
+    }
+    let (name, offset) = match text.find(['+', '-']) {
+        Some(index) => {
+            let offset = text[index + 1..].trim().parse::<i128>().ok()?;
+            let sign = if text[index..].starts_with('-') { -1 } else { 1 };
+            (text[..index].trim(), sign * offset)
+        }
+        None => (text, 0),
+    };
+    if is_symbol(name) {
+        Some(Expr::Symbol(name.to_string(), offset))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::{Automaton, RunState};
+    use crate::intcode::asm::{assemble, AsmError, AsmErrorKind};
+    use crate::intcode::disasm::disassemble;
+
+    #[test]
+    fn test_assemble_jump_program() {
+        let source = "
+                in -> @3            ; overwrite the condition of the jump
+                jt #-1, #nonzero
+                add #0, #0 -> @result
+        nonzero:
+                out @result
+                hlt
+        result: data 1
+        ";
+        assert_eq!(assemble(source), Ok(String::from("3,3,1105,-1,9,1101,0,0,12,4,12,99,1")));
+    }
+
+    #[test]
+    fn test_relative_operands_and_symbols() {
+        let source = "
+        base = 100
+                arb #base
+                ADD [r+1], [rb-1], [r]
+                mul [r + top], #2 -> table+1
+                hlt
+        table:  data -5, top, table
+        top:
+        ";
+        assert_eq!(assemble(source), Ok(String::from("109,100,22201,1,-1,0,1202,14,2,12,99,-5,14,11")));
+    }
+
+    #[test]
+    fn test_assembled_program_runs() {
+        let source = "
+                in -> counter
+        loop:   out counter
+                add counter, #-1 -> counter
+                jt counter, #loop
+                hlt
+        counter: data 0
+        ";
+        let program = assemble(source).unwrap();
+        let mut automaton = Automaton::new_with_program(&program).add_initial_input(3);
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.get_output(3), Some(vec![3, 2, 1]));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(assemble("nop"), Err(AsmError { line: 1, kind: AsmErrorKind::UnknownMnemonic(String::from("nop")) }));
+        assert_eq!(assemble("\nadd #1, #2"), Err(AsmError { line: 2, kind: AsmErrorKind::WrongOperandCount { expected: 3, found: 2 } }));
+        assert_eq!(assemble("in #4"), Err(AsmError { line: 1, kind: AsmErrorKind::ImmediateDestination }));
+        assert_eq!(assemble("out $4"), Err(AsmError { line: 1, kind: AsmErrorKind::InvalidOperand(String::from("$4")) }));
+        assert_eq!(assemble("a: hlt\na: hlt"), Err(AsmError { line: 2, kind: AsmErrorKind::DuplicateSymbol(String::from("a")) }));
+        assert_eq!(assemble("jt #1, #nowhere"), Err(AsmError { line: 1, kind: AsmErrorKind::UndefinedSymbol(String::from("nowhere")) }));
+    }
+
+    #[test]
+    fn test_round_trip_disassembly() {
+        let program = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99,21101,5,-3,20,0,11101";
+        let source = disassemble(&Automaton::new_with_program(program)).iter()
+            .map(|line| line.source())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(assemble(&source), Ok(String::from(program)));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::intcode::{Automaton, IntcodeError, RunOutcome, RunState};
+use crate::intcode::frame::Frame;
+use crate::intcode::word::FixedWord;
+
+/// A device attached to an Intcode program: it consumes the program's output
+/// as decoded frames and answers its requests for input. A controller holds
+/// no machine, so it can be tested by feeding it frames.
+pub trait Controller {
+    /// What one group of output values decodes to, e.g. `(i128, i128, Tile)`.
+    type Frame: Frame;
+
+    fn on_output(&mut self, frame: Self::Frame);
+
+    /// The value for the next `IN` instruction. `None` leaves the machine
+    /// waiting and ends `drive`.
+    fn next_input(&mut self) -> Option<i128>;
+
+    /// Checked after every frame; `drive` returns as soon as it is true.
+    fn is_done(&self) -> bool {
+        false
+    }
+}
+
+/// Runs `automaton` with `controller` attached until the program halts, the
+/// controller is done or has no input to give. Every complete frame is handed
+/// to the controller before it is asked for input. Returns the state of the
+/// machine at that point, or an `IncompleteFrame` error if the program halts
+/// in the middle of a frame. An input that does not fit the machine's word
+/// type faults with `IntcodeError::Overflow`.
+pub fn drive<W: FixedWord>(automaton: &mut Automaton<W>, controller: &mut impl Controller) -> RunOutcome {
+    loop {
+        let state = automaton.run()?;
+        while let Some(frame) = automaton.read_frame()? {
+            controller.on_output(frame);
+            if controller.is_done() {
+                return Ok(state);
+            }
+        }
+
+        match state {
+            RunState::AwaitingInput => match controller.next_input() {
+                Some(input) => {
+                    let input = W::try_from(input).map_err(|_| IntcodeError::Overflow { pc: automaton.pc })?;
+                    automaton.runtime_input(input)?
+                }
+                None => return Ok(state),
+            },
+            state => return Ok(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::{Automaton, RunOutcome, RunState};
+    use crate::intcode::controller::{drive, Controller};
+
+    // reads a value n and outputs the pair (n, n * n) until n is 0
+    const SQUARES: &str = "3,20,1006,20,16,4,20,2,20,20,21,4,21,1105,1,0,99";
+
+    struct Squares {
+        inputs: Vec<i128>,
+        frames: Vec<(i128, i128)>,
+        limit: usize,
+    }
+
+    impl Controller for Squares {
+        type Frame = (i128, i128);
+
+        fn on_output(&mut self, frame: (i128, i128)) {
+            self.frames.push(frame);
+        }
+
+        fn next_input(&mut self) -> Option<i128> {
+            self.inputs.pop()
+        }
+
+        fn is_done(&self) -> bool {
+            self.frames.len() >= self.limit
+        }
+    }
+
+    fn squares(inputs: Vec<i128>, limit: usize) -> (Squares, RunOutcome) {
+        let mut controller = Squares { inputs, frames: vec![], limit };
+        let outcome = drive(&mut Automaton::new_with_program(SQUARES), &mut controller);
+        (controller, outcome)
+    }
+
+    #[test]
+    fn test_drive_until_halted() {
+        let (controller, outcome) = squares(vec![0, -4, 3], usize::MAX);
+        assert_eq!(outcome, Ok(RunState::Halted));
+        assert_eq!(controller.frames, vec![(3, 9), (-4, 16)]);
+    }
+
+    #[test]
+    fn test_drive_until_out_of_input() {
+        let (controller, outcome) = squares(vec![5], usize::MAX);
+        assert_eq!(outcome, Ok(RunState::AwaitingInput));
+        assert_eq!(controller.frames, vec![(5, 25)]);
+    }
+
+    #[test]
+    fn test_drive_until_done() {
+        let (controller, outcome) = squares(vec![0, 2, 1], 1);
+        assert_eq!(outcome, Ok(RunState::AwaitingInput));
+        assert_eq!(controller.frames, vec![(1, 1)]);
+        assert_eq!(controller.inputs, vec![0, 2]);
+    }
+}
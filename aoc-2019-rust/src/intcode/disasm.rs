@@ -0,0 +1,178 @@
+use std::fmt;
+use crate::intcode::{Automaton, ParameterMode};
+use crate::intcode::word::FixedWord;
+
+/// A single parameter as it appears in a listing: `@12` reads address 12,
+/// `#5` is the literal 5 and `[r+3]` is 3 past the relative base.
+#[derive(Debug, Copy, Clone)]
+pub struct Operand {
+    pub mode: ParameterMode,
+    pub value: i128,
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            ParameterMode::Position => write!(f, "@{}", self.value),
+            ParameterMode::Immediate => write!(f, "#{}", self.value),
+            ParameterMode::Relative if self.value < 0 => write!(f, "[r{}]", self.value),
+            ParameterMode::Relative => write!(f, "[r+{}]", self.value),
+        }
+    }
+}
+
+/// One line of a listing: either a decodable instruction or a raw data word.
+#[derive(Debug, Clone)]
+pub enum Line {
+    Instruction {
+        address: u128,
+        words: Vec<i128>,
+        mnemonic: &'static str,
+        operands: Vec<Operand>,
+        writes: bool,
+    },
+    Data {
+        address: u128,
+        value: i128,
+    },
+}
+
+impl Line {
+    pub fn address(&self) -> u128 {
+        match self {
+            Line::Instruction { address, .. } | Line::Data { address, .. } => *address,
+        }
+    }
+
+    /// Number of memory words covered by this line.
+    pub fn size(&self) -> u128 {
+        match self {
+            Line::Instruction { words, .. } => words.len() as u128,
+            Line::Data { .. } => 1,
+        }
+    }
+
+    /// The instruction without address and raw words, in the syntax accepted
+    /// by `asm::assemble`.
+    pub fn source(&self) -> String {
+        match self {
+            Line::Instruction { mnemonic, operands, writes, .. } => {
+                let (reads, dest) = match (writes, operands.split_last()) {
+                    (true, Some((dest, reads))) => (reads, Some(dest)),
+                    _ => (operands.as_slice(), None),
+                };
+                let reads = reads.iter().map(|operand| operand.to_string()).collect::<Vec<_>>().join(", ");
+                match (reads.is_empty(), dest) {
+                    (true, Some(dest)) => format!("{mnemonic} -> {dest}"),
+                    (false, Some(dest)) => format!("{mnemonic} {reads} -> {dest}"),
+                    (true, None) => mnemonic.to_string(),
+                    (false, None) => format!("{mnemonic} {reads}"),
+                }
+            }
+            Line::Data { value, .. } => format!("DATA {value}"),
+        }
+    }
+
+    fn words(&self) -> Vec<i128> {
+        match self {
+            Line::Instruction { words, .. } => words.clone(),
+            Line::Data { value, .. } => vec![*value],
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = self.words().iter().map(|word| word.to_string()).collect::<Vec<_>>().join(",");
+        write!(f, "{:>6}  {raw:<28}{}", self.address(), self.source())
+    }
+}
+
+/// Decodes the word at `address`. Words that are not a valid instruction, or
+/// whose parameters run past the end of the loaded memory, become `Data`.
+pub fn disassemble_at<W: FixedWord>(automaton: &Automaton<W>, address: u128) -> Line {
+    let data = Line::Data { address, value: automaton.read_from_address(address).into() };
+    let Ok(instruction) = automaton.decode_at(address) else {
+        return data;
+    };
+    let Some(def) = automaton.instruction_set.get(&instruction.opcode) else {
+        return data;
+    };
+    if address + instruction.len() > automaton.memory.len() {
+        return data;
+    }
+
+    let operands : Vec<Operand> = instruction.params[..def.no_params as usize].iter()
+        .map(|param| Operand { mode: param.mode, value: automaton.read_from_address(param.address).into() })
+        .collect();
+    if def.writes && matches!(operands.last(), Some(Operand { mode: ParameterMode::Immediate, .. })) {
+        return data;
+    }
+
+    Line::Instruction {
+        address,
+        words: (address..address + instruction.len()).map(|a| automaton.read_from_address(a).into()).collect(),
+        mnemonic: def.mnemonic,
+        operands,
+        writes: def.writes,
+    }
+}
+
+/// Walks the whole memory image linearly from address 0.
+pub fn disassemble<W: FixedWord>(automaton: &Automaton<W>) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut address = 0;
+    while address < automaton.memory.len() {
+        let line = disassemble_at(automaton, address);
+        address += line.size();
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::Automaton;
+    use crate::intcode::disasm::disassemble;
+
+    fn listing(program: &str) -> Vec<String> {
+        disassemble(&Automaton::new_with_program(program)).iter()
+            .map(|line| line.source())
+            .collect()
+    }
+
+    #[test]
+    fn test_mnemonics_and_modes() {
+        assert_eq!(listing("21101,5,-3,20,3,7,204,-2,1005,9,0,109,4,99"), vec![
+            "ADD #5, #-3 -> [r+20]",
+            "IN -> @7",
+            "OUT [r-2]",
+            "JT @9, #0",
+            "ARB #4",
+            "HLT",
+        ]);
+    }
+
+    #[test]
+    fn test_data_fallback() {
+        assert_eq!(listing("1101,1,1,20,99,0,-7,11101,0,0,0,2,5"), vec![
+            "ADD #1, #1 -> @20",
+            "HLT",
+            "DATA 0",
+            "DATA -7",
+            "DATA 11101",
+            "DATA 0",
+            "DATA 0",
+            "DATA 0",
+            "DATA 2",
+            "DATA 5",
+        ]);
+    }
+
+    #[test]
+    fn test_addresses_and_raw_words() {
+        let lines = disassemble(&Automaton::new_with_program("1002,4,3,4,33"));
+        assert_eq!(lines[0].to_string().trim_end(), format!("{:>6}  {:<28}MUL @4, #3 -> @4", 0, "1002,4,3,4"));
+        assert_eq!(lines[1].to_string(), format!("{:>6}  {:<28}DATA 33", 4, "33"));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::intcode::{Automaton, IntcodeError, RunState};
+use crate::intcode::word::FixedWord;
+
+/// A fixed number of consecutive output values decoded into one value, e.g.
+/// the `(x, y, tile)` triples of a game screen.
+///
+/// Implemented for `i128`, for arrays of `i128` and for pairs and triples of
+/// any types that convert `From<i128>`. Narrower output words are widened to
+/// `i128` before decoding.
+pub trait Frame: Sized {
+    const SIZE: usize;
+
+    /// Decodes exactly `SIZE` values.
+    fn decode(values: &[i128]) -> Self;
+}
+
+impl Frame for i128 {
+    const SIZE: usize = 1;
+
+    fn decode(values: &[i128]) -> Self {
+        values[0]
+    }
+}
+
+impl<const N: usize> Frame for [i128; N] {
+    const SIZE: usize = N;
+
+    fn decode(values: &[i128]) -> Self {
+        let mut frame = [0; N];
+        frame.copy_from_slice(values);
+        frame
+    }
+}
+
+impl<A: From<i128>, B: From<i128>> Frame for (A, B) {
+    const SIZE: usize = 2;
+
+    fn decode(values: &[i128]) -> Self {
+        (A::from(values[0]), B::from(values[1]))
+    }
+}
+
+impl<A: From<i128>, B: From<i128>, C: From<i128>> Frame for (A, B, C) {
+    const SIZE: usize = 3;
+
+    fn decode(values: &[i128]) -> Self {
+        (A::from(values[0]), B::from(values[1]), C::from(values[2]))
+    }
+}
+
+impl<W: FixedWord> Automaton<W> {
+    /// Decodes the next unread frame of output. Returns `None` while the
+    /// frame is not complete yet, and an `IncompleteFrame` error if it never
+    /// will be because the machine has halted.
+    pub fn read_frame<F: Frame>(&mut self) -> Result<Option<F>, IntcodeError> {
+        let unread = self.output.len() - self.output_curr_index;
+        if unread >= F::SIZE {
+            let start = self.output_curr_index;
+            self.output_curr_index += F::SIZE;
+            let values : Vec<i128> = self.output.range(start..start + F::SIZE).map(|&value| value.into()).collect();
+            Ok(Some(F::decode(&values)))
+        } else if unread > 0 && self.state == RunState::Halted {
+            Err(IntcodeError::IncompleteFrame { pc: self.pc, size: F::SIZE, found: unread })
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Decodes all complete unread frames of output.
+    pub fn read_frames<F: Frame>(&mut self) -> Result<Vec<F>, IntcodeError> {
+        let mut frames = Vec::new();
+        while let Some(frame) = self.read_frame()? {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::{Automaton, IntcodeError};
+
+    #[derive(Debug, PartialEq)]
+    enum Light {
+        Off,
+        On,
+    }
+
+    impl From<i128> for Light {
+        fn from(value: i128) -> Self {
+            if value == 0 { Light::Off } else { Light::On }
+        }
+    }
+
+    fn halted_with_outputs(outputs: &str) -> Automaton {
+        let program = outputs.split(',').map(|value| format!("104,{value},")).collect::<String>() + "99";
+        let mut automaton = Automaton::new_with_program(&program);
+        automaton.run().unwrap();
+        automaton
+    }
+
+    #[test]
+    fn test_read_typed_frames() {
+        let mut automaton = halted_with_outputs("3,4,1,-1,0,0,7");
+        assert_eq!(automaton.read_frame::<(i128, i128, Light)>(), Ok(Some((3, 4, Light::On))));
+        assert_eq!(automaton.read_frame::<(i128, i128, Light)>(), Ok(Some((-1, 0, Light::Off))));
+        assert_eq!(automaton.read_frame::<i128>(), Ok(Some(7)));
+        assert_eq!(automaton.read_frame::<i128>(), Ok(None));
+    }
+
+    #[test]
+    fn test_read_frames() {
+        let mut automaton = halted_with_outputs("1,2,3,4,5,6");
+        assert_eq!(automaton.read_frames::<[i128; 3]>(), Ok(vec![[1, 2, 3], [4, 5, 6]]));
+    }
+
+    #[test]
+    fn test_incomplete_frame() {
+        let mut automaton = halted_with_outputs("1,2,3,4,5");
+        assert_eq!(automaton.read_frames::<(i128, Light)>(), Err(IntcodeError::IncompleteFrame { pc: 10, size: 2, found: 1 }));
+
+        let mut waiting = Automaton::new_with_program("104,1,3,0,99");
+        waiting.run().unwrap();
+        assert_eq!(waiting.read_frames::<(i128, Light)>(), Ok(vec![]));
+    }
+}
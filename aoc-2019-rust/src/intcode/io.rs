@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, Sender};
+use crate::intcode::{Automaton, IntcodeError};
+use crate::intcode::word::Word;
+
+/// Supplies values to `IN` instructions once the machine's own input queue is
+/// empty. Returning `None` makes the machine wait in `RunState::AwaitingInput`;
+/// the source is asked again on the next `run`.
+pub trait InputSource: Send {
+    fn next_input(&mut self) -> Option<i128>;
+}
+
+/// Receives every value of an `OUT` instruction as soon as it is executed,
+/// instead of the machine's output buffer.
+pub trait OutputSink: Send {
+    fn put_output(&mut self, value: i128);
+}
+
+impl<F: FnMut() -> Option<i128> + Send> InputSource for F {
+    fn next_input(&mut self) -> Option<i128> {
+        self()
+    }
+}
+
+impl<F: FnMut(i128) + Send> OutputSink for F {
+    fn put_output(&mut self, value: i128) {
+        self(value)
+    }
+}
+
+/// Feeds the values of an iterator, e.g. `IterInput((0..10).map(i128::from))`.
+pub struct IterInput<I>(pub I);
+
+impl<I: Iterator<Item = i128> + Send> InputSource for IterInput<I> {
+    fn next_input(&mut self) -> Option<i128> {
+        self.0.next()
+    }
+}
+
+/// Blocks until a value arrives. A closed channel counts as no input.
+impl InputSource for Receiver<i128> {
+    fn next_input(&mut self) -> Option<i128> {
+        self.recv().ok()
+    }
+}
+
+/// Values sent after the receiving end has gone away are dropped.
+impl OutputSink for Sender<i128> {
+    fn put_output(&mut self, value: i128) {
+        let _ = self.send(value);
+    }
+}
+
+/// A queue that can be handed to a machine as source or sink while a clone is
+/// kept to look at or fill it from the outside.
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer {
+    values: Arc<Mutex<VecDeque<i128>>>,
+}
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, value: i128) {
+        self.values.lock().unwrap().push_back(value);
+    }
+
+    pub fn pop(&self) -> Option<i128> {
+        self.values.lock().unwrap().pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes and returns all queued values.
+    pub fn take(&self) -> Vec<i128> {
+        self.values.lock().unwrap().drain(..).collect()
+    }
+}
+
+impl InputSource for SharedBuffer {
+    fn next_input(&mut self) -> Option<i128> {
+        self.pop()
+    }
+}
+
+impl OutputSink for SharedBuffer {
+    fn put_output(&mut self, value: i128) {
+        self.push(value);
+    }
+}
+
+impl<W: Word> Automaton<W> {
+    /// Asks `source` for input whenever the input queue is empty. Values
+    /// queued with `add_initial_input` or `runtime_input` are still read first.
+    pub fn with_input_source(mut self, source: impl InputSource + 'static) -> Self {
+        self.input_source = Some(Box::new(source));
+
+        self
+    }
+
+    /// Hands every output to `sink` instead of the output buffer, so
+    /// `get_output` and friends no longer see them.
+    pub fn with_output_sink(mut self, sink: impl OutputSink + 'static) -> Self {
+        self.output_sink = Some(Box::new(sink));
+
+        self
+    }
+
+    /// Removes the input source and output sink, returning to plain buffers.
+    pub fn detach_io(&mut self) {
+        self.input_source = None;
+        self.output_sink = None;
+    }
+
+    pub(crate) fn can_read_input(&self) -> bool {
+        !self.input.is_empty() || self.input_source.is_some()
+    }
+
+    /// Values from the source that do not fit the word type fault.
+    pub(crate) fn read_input(&mut self) -> Result<Option<W>, IntcodeError> {
+        if let Some(value) = self.input.pop_front() {
+            return Ok(Some(value));
+        }
+        match self.input_source.as_mut().and_then(|source| source.next_input()) {
+            Some(value) => W::try_from(value).map(Some).map_err(|_| IntcodeError::Overflow { pc: self.pc }),
+            None => Ok(None),
+        }
+    }
+
+    /// Values that do not fit the `i128` of a sink fault.
+    pub(crate) fn write_output(&mut self, value: W) -> Result<(), IntcodeError> {
+        match self.output_sink.is_some() {
+            true => {
+                let value = self.to_i128(&value)?;
+                if let Some(sink) = self.output_sink.as_mut() {
+                    sink.put_output(value);
+                }
+            }
+            false => self.output.push_back(value),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+    use crate::intcode::{Automaton, RunState};
+    use crate::intcode::io::{IterInput, SharedBuffer};
+
+    // doubles every input until it reads a 0
+    const DOUBLER: &str = "3,20,1006,20,14,102,2,20,21,4,21,1105,1,0,99";
+
+    #[test]
+    fn test_closure_source_and_sink() {
+        let outputs = SharedBuffer::new();
+        let sink = outputs.clone();
+        let mut inputs = vec![0, 3, 2, 1];
+        let mut automaton = Automaton::new_with_program(DOUBLER)
+            .with_input_source(move || inputs.pop())
+            .with_output_sink(move |value| sink.push(value));
+
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(outputs.take(), vec![2, 4, 6]);
+        assert!(!automaton.has_output());
+    }
+
+    #[test]
+    fn test_queued_input_comes_first() {
+        let mut automaton = Automaton::new_with_program(DOUBLER)
+            .add_initial_input(5)
+            .with_input_source(IterInput([7, 0].into_iter()));
+
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert_eq!(automaton.get_output(2), Some(vec![10, 14]));
+    }
+
+    #[test]
+    fn test_exhausted_source_waits_for_input() {
+        let source = SharedBuffer::new();
+        source.push(4);
+        let mut automaton = Automaton::new_with_program(DOUBLER).with_input_source(source.clone());
+
+        assert_eq!(automaton.run(), Ok(RunState::AwaitingInput));
+        assert_eq!(automaton.get_output(1), Some(vec![8]));
+
+        source.push(0);
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+    }
+
+    #[test]
+    fn test_sink_responds_to_each_output() {
+        // the device answers every output with that output plus one
+        let replies = SharedBuffer::new();
+        let device = replies.clone();
+        let mut automaton = Automaton::new_with_program(DOUBLER)
+            .add_initial_input(1)
+            .with_input_source(replies.clone())
+            .with_output_sink(move |value: i128| if value < 50 { device.push(value + 1) } else { device.push(0) });
+
+        assert_eq!(automaton.run(), Ok(RunState::Halted));
+        assert!(replies.is_empty());
+    }
+
+    #[test]
+    fn test_channels() {
+        let (to_machine, input) = mpsc::channel();
+        let (output, from_machine) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let mut automaton = Automaton::new_with_program(DOUBLER)
+                .with_input_source(input)
+                .with_output_sink(output);
+            automaton.run()
+        });
+
+        for value in [3, 5] {
+            to_machine.send(value).unwrap();
+            assert_eq!(from_machine.recv(), Ok(value * 2));
+        }
+        to_machine.send(0).unwrap();
+        assert_eq!(handle.join().unwrap(), Ok(RunState::Halted));
+        assert!(from_machine.recv().is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::intcode::word::Word;
+
+/// Word-addressed storage backing an `Automaton`. Unwritten cells read as 0.
+pub trait Memory<W: Word = i128>: Send {
+    fn read(&self, address: u128) -> W;
+
+    fn write(&mut self, address: u128, value: W);
+
+    /// One past the highest address ever written.
+    fn len(&self) -> u128;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// All non-zero cells in ascending address order.
+    fn cells(&self) -> Vec<(u128, W)>;
+
+    /// An independent copy. Writes to either copy are not seen by the other.
+    fn fork(&self) -> Box<dyn Memory<W>>;
+}
+
+/// Writes every cell of `from` to `to`, converted by `convert`. `cells()`
+/// skips zeros, so the last address is written first to keep the length.
+pub(crate) fn copy_memory<A: Word, B: Word>(from: &dyn Memory<A>, to: &mut dyn Memory<B>, convert: impl Fn(A) -> B) {
+    if !from.is_empty() {
+        to.write(from.len() - 1, B::ZERO);
+    }
+    for (address, value) in from.cells() {
+        to.write(address, convert(value));
+    }
+}
+
+/// Addresses below this limit are paged through a vector, above it through a map.
+const DENSE_LIMIT: u128 = 1 << 16;
+const PAGE_SIZE: usize = 256;
+
+type Page<W> = [W; PAGE_SIZE];
+
+/// Default backend: fixed-size pages in a growable vector for the program
+/// image and the scratch space near it, and in a map for far addresses.
+///
+/// Pages are shared copy-on-write, so `fork` only copies the page table and a
+/// forked machine pays for a page the first time either copy writes to it.
+#[derive(Clone)]
+pub struct DenseMemory<W: Word = i128> {
+    pages: Vec<Arc<Page<W>>>,
+    far_pages: HashMap<u128, Arc<Page<W>>>,
+    zero_page: Arc<Page<W>>,
+    len: u128,
+}
+
+impl<W: Word> Default for DenseMemory<W> {
+    fn default() -> Self {
+        Self {
+            pages: Vec::new(),
+            far_pages: HashMap::new(),
+            zero_page: Arc::new([W::ZERO; PAGE_SIZE]),
+            len: 0,
+        }
+    }
+}
+
+impl DenseMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<W: Word> DenseMemory<W> {
+    fn page_of(address: u128) -> (u128, usize) {
+        (address / PAGE_SIZE as u128, (address % PAGE_SIZE as u128) as usize)
+    }
+}
+
+impl<W: Word> Memory<W> for DenseMemory<W> {
+    fn read(&self, address: u128) -> W {
+        let (page, offset) = Self::page_of(address);
+        if address < DENSE_LIMIT {
+            self.pages.get(page as usize).map_or(W::ZERO, |page| page[offset].clone())
+        } else {
+            self.far_pages.get(&page).map_or(W::ZERO, |page| page[offset].clone())
+        }
+    }
+
+    fn write(&mut self, address: u128, value: W) {
+        let (page, offset) = Self::page_of(address);
+        let page = if address < DENSE_LIMIT {
+            let index = page as usize;
+            if index >= self.pages.len() {
+                self.pages.resize(index + 1, self.zero_page.clone());
+            }
+            &mut self.pages[index]
+        } else {
+            self.far_pages.entry(page).or_insert_with(|| self.zero_page.clone())
+        };
+        Arc::make_mut(page)[offset] = value;
+        self.len = self.len.max(address + 1);
+    }
+
+    fn len(&self) -> u128 {
+        self.len
+    }
+
+    fn cells(&self) -> Vec<(u128, W)> {
+        let mut far : Vec<(&u128, &Arc<Page<W>>)> = self.far_pages.iter().collect();
+        far.sort_unstable_by_key(|&(&page, _)| page);
+
+        self.pages.iter()
+            .enumerate()
+            .map(|(page, words)| (page as u128, words))
+            .chain(far.into_iter().map(|(&page, words)| (page, words)))
+            .flat_map(|(page, words)| words.iter()
+                .enumerate()
+                .filter(|(_, value)| **value != W::ZERO)
+                .map(move |(offset, value)| (page * PAGE_SIZE as u128 + offset as u128, value.clone())))
+            .collect()
+    }
+
+    fn fork(&self) -> Box<dyn Memory<W>> {
+        Box::new(self.clone())
+    }
+}
+
+/// `HashMap` backend, the original storage of the `Automaton`. Only touched
+/// cells take up space, at the cost of hashing on every access.
+#[derive(Clone)]
+pub struct SparseMemory<W: Word = i128> {
+    words: HashMap<u128, W>,
+    len: u128,
+}
+
+impl<W: Word> Default for SparseMemory<W> {
+    fn default() -> Self {
+        Self {
+            words: HashMap::new(),
+            len: 0,
+        }
+    }
+}
+
+impl SparseMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<W: Word> Memory<W> for SparseMemory<W> {
+    fn read(&self, address: u128) -> W {
+        self.words.get(&address).cloned().unwrap_or(W::ZERO)
+    }
+
+    fn write(&mut self, address: u128, value: W) {
+        self.words.insert(address, value);
+        self.len = self.len.max(address + 1);
+    }
+
+    fn len(&self) -> u128 {
+        self.len
+    }
+
+    fn cells(&self) -> Vec<(u128, W)> {
+        let mut cells : Vec<(u128, W)> = self.words.iter()
+            .filter(|(_, value)| **value != W::ZERO)
+            .map(|(&address, value)| (address, value.clone()))
+            .collect();
+        cells.sort_unstable();
+        cells
+    }
+
+    fn fork(&self) -> Box<dyn Memory<W>> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::intcode::memory::{DenseMemory, Memory, SparseMemory};
+
+    fn exercise(memory: &mut dyn Memory) {
+        assert!(memory.is_empty());
+        assert_eq!(memory.read(5), 0);
+
+        memory.write(3, 7);
+        memory.write(1, -2);
+        memory.write(1 << 40, 9);
+        memory.write((1 << 40) + 1, 0);
+
+        assert_eq!(memory.read(3), 7);
+        assert_eq!(memory.read(1), -2);
+        assert_eq!(memory.read(1 << 40), 9);
+        assert_eq!(memory.read((1 << 40) - 1), 0);
+        assert_eq!(memory.len(), (1 << 40) + 2);
+        assert_eq!(memory.cells(), vec![(1, -2), (3, 7), (1 << 40, 9)]);
+    }
+
+    fn exercise_fork(memory: &mut dyn Memory) {
+        memory.write(2, 5);
+        memory.write(1 << 40, 6);
+        let mut fork = memory.fork();
+
+        fork.write(2, 50);
+        memory.write(1 << 40, 60);
+        fork.write(3, 7);
+
+        assert_eq!(memory.cells(), vec![(2, 5), (1 << 40, 60)]);
+        assert_eq!(fork.cells(), vec![(2, 50), (3, 7), (1 << 40, 6)]);
+    }
+
+    #[test]
+    fn test_dense_memory() {
+        exercise(&mut DenseMemory::new());
+        exercise_fork(&mut DenseMemory::new());
+    }
+
+    #[test]
+    fn test_sparse_memory() {
+        exercise(&mut SparseMemory::new());
+        exercise_fork(&mut SparseMemory::new());
+    }
+
+    #[test]
+    fn test_dense_fork_shares_untouched_pages() {
+        let mut memory = DenseMemory::new();
+        (0..1000).for_each(|address| memory.write(address, address as i128));
+        let mut fork = memory.clone();
+        fork.write(999, 0);
+
+        let shared = memory.pages.iter().zip(&fork.pages)
+            .filter(|(left, right)| Arc::ptr_eq(left, right))
+            .count();
+        assert_eq!(shared, memory.pages.len() - 1);
+    }
+}
@@ -0,0 +1,334 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use crate::intcode::{Automaton, IntcodeError, RunState};
+use crate::intcode::io::{InputSource, OutputSink, SharedBuffer};
+
+/// Machines that each run on their own thread, with the outputs of a machine
+/// sent over `mpsc` channels to the inputs of the machines it is connected to.
+///
+/// A machine connected to several others sends each output to all of them;
+/// outputs of several machines into one arrive in the order they were sent.
+/// The channels carry `i128`, so all machines use the default word type.
+#[derive(Default)]
+pub struct Network {
+    machines: Vec<Automaton>,
+    links: Vec<Vec<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkError {
+    /// Every machine that has not halted waits for input no machine can send.
+    Deadlock { blocked: Vec<usize> },
+    Fault { machine: usize, error: IntcodeError },
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::Deadlock { blocked } => write!(f, "deadlock, machines {blocked:?} wait for input that never comes"),
+            NetworkError::Fault { machine, error } => write!(f, "machine {machine} faulted: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl Network {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Each machine feeds the next one.
+    pub fn pipeline(machines: Vec<Automaton>) -> Self {
+        let mut network = Network::new();
+        let ids : Vec<usize> = machines.into_iter().map(|machine| network.add(machine)).collect();
+        for pair in ids.windows(2) {
+            network.connect(pair[0], pair[1]);
+        }
+        network
+    }
+
+    /// A pipeline whose last machine feeds the first one again.
+    pub fn ring(machines: Vec<Automaton>) -> Self {
+        let count = machines.len();
+        let mut network = Network::pipeline(machines);
+        if count > 0 {
+            network.connect(count - 1, 0);
+        }
+        network
+    }
+
+    /// Machine 0 is `source`, every output of it goes to all of the `sinks`.
+    pub fn fan_out(source: Automaton, sinks: Vec<Automaton>) -> Self {
+        let mut network = Network::new();
+        let source = network.add(source);
+        for sink in sinks {
+            let sink = network.add(sink);
+            network.connect(source, sink);
+        }
+        network
+    }
+
+    /// Adds a machine and returns its id, which is its index in the result of `run`.
+    pub fn add(&mut self, automaton: Automaton) -> usize {
+        self.machines.push(automaton);
+        self.links.push(Vec::new());
+        self.machines.len() - 1
+    }
+
+    /// Sends the outputs of machine `from` to the input of machine `to`.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        self.links[from].push(to);
+    }
+
+    /// Runs all machines until each has halted. Outputs stay readable in the
+    /// output buffer of the machine that produced them, input values a
+    /// machine did not get to read are dropped.
+    pub fn run(self) -> Result<Vec<Automaton>, NetworkError> {
+        let (senders, receivers) : (Vec<Sender<Message>>, Vec<Receiver<Message>>) = self.machines.iter()
+            .map(|_| mpsc::channel())
+            .unzip();
+        let monitor = Arc::new(Monitor {
+            status: Mutex::new(Status::default()),
+            machines: self.machines.len(),
+            inputs: Mutex::new(senders.clone()),
+        });
+
+        let handles : Vec<_> = self.machines.into_iter()
+            .zip(receivers)
+            .zip(self.links)
+            .enumerate()
+            .map(|(id, ((automaton, receiver), links))| {
+                let input = ChannelInput { receiver: Some(receiver), monitor: monitor.clone() };
+                let record = SharedBuffer::new();
+                let output = ChannelOutput {
+                    targets: links.iter().map(|&to| senders[to].clone()).collect(),
+                    record: record.clone(),
+                    monitor: monitor.clone(),
+                };
+                let monitor = monitor.clone();
+
+                thread::spawn(move || {
+                    let mut automaton = automaton.with_input_source(input).with_output_sink(output);
+                    let outcome = automaton.run();
+                    automaton.detach_io();
+                    automaton.output.extend(record.take());
+                    monitor.finished();
+                    (outcome.map_err(|error| NetworkError::Fault { machine: id, error }), automaton)
+                })
+            })
+            .collect();
+        drop(senders);
+
+        let mut machines = Vec::new();
+        let mut fault = None;
+        for handle in handles {
+            let (outcome, automaton) = handle.join().expect("Intcode machine thread panicked");
+            if let Err(error) = outcome {
+                fault.get_or_insert(error);
+            }
+            machines.push(automaton);
+        }
+
+        if let Some(error) = fault {
+            return Err(error);
+        }
+        let blocked : Vec<usize> = machines.iter()
+            .enumerate()
+            .filter(|(_, machine)| machine.state() == RunState::AwaitingInput)
+            .map(|(id, _)| id)
+            .collect();
+        if blocked.is_empty() {
+            Ok(machines)
+        } else {
+            Err(NetworkError::Deadlock { blocked })
+        }
+    }
+}
+
+enum Message {
+    Value(i128),
+    /// Wakes up a blocked machine so that it stops waiting.
+    Shutdown,
+}
+
+#[derive(Default)]
+struct Status {
+    blocked: usize,
+    finished: usize,
+    /// Values sent but not received yet.
+    in_flight: usize,
+    deadlocked: bool,
+}
+
+/// Shared bookkeeping of all threads, to tell a machine that waits for a value
+/// on its way from one that waits for a value no machine can send anymore.
+struct Monitor {
+    status: Mutex<Status>,
+    machines: usize,
+    inputs: Mutex<Vec<Sender<Message>>>,
+}
+
+impl Monitor {
+    fn blocked(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.blocked += 1;
+        self.detect_deadlock(&mut status);
+    }
+
+    fn received(&self, was_blocked: bool) {
+        let mut status = self.status.lock().unwrap();
+        status.in_flight -= 1;
+        if was_blocked {
+            status.blocked -= 1;
+        }
+    }
+
+    fn finished(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.finished += 1;
+        self.detect_deadlock(&mut status);
+    }
+
+    fn send(&self, target: &Sender<Message>, value: i128) {
+        let mut status = self.status.lock().unwrap();
+        if target.send(Message::Value(value)).is_ok() {
+            status.in_flight += 1;
+        }
+    }
+
+    fn detect_deadlock(&self, status: &mut Status) {
+        if status.blocked > 0 && status.blocked + status.finished == self.machines && status.in_flight == 0 && !status.deadlocked {
+            status.deadlocked = true;
+            for input in self.inputs.lock().unwrap().drain(..) {
+                let _ = input.send(Message::Shutdown);
+            }
+        }
+    }
+}
+
+struct ChannelInput {
+    receiver: Option<Receiver<Message>>,
+    monitor: Arc<Monitor>,
+}
+
+impl InputSource for ChannelInput {
+    fn next_input(&mut self) -> Option<i128> {
+        let receiver = self.receiver.as_ref()?;
+        let (message, was_blocked) = match receiver.try_recv() {
+            Ok(message) => (message, false),
+            Err(TryRecvError::Disconnected) => return None,
+            Err(TryRecvError::Empty) => {
+                self.monitor.blocked();
+                (receiver.recv().ok()?, true)
+            }
+        };
+        match message {
+            Message::Value(value) => {
+                self.monitor.received(was_blocked);
+                Some(value)
+            }
+            Message::Shutdown => None,
+        }
+    }
+}
+
+/// Values still queued when a machine stops are no longer in flight. They are
+/// discarded with the receiver under the lock `send` holds, so no value can
+/// slip in between.
+impl Drop for ChannelInput {
+    fn drop(&mut self) {
+        let mut status = self.monitor.status.lock().unwrap();
+        if let Some(receiver) = self.receiver.take() {
+            status.in_flight -= receiver.try_iter()
+                .filter(|message| matches!(message, Message::Value(_)))
+                .count();
+        }
+    }
+}
+
+struct ChannelOutput {
+    targets: Vec<Sender<Message>>,
+    record: SharedBuffer,
+    monitor: Arc<Monitor>,
+}
+
+impl OutputSink for ChannelOutput {
+    fn put_output(&mut self, value: i128) {
+        self.record.push(value);
+        for target in &self.targets {
+            self.monitor.send(target, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::{Automaton, IntcodeError};
+    use crate::intcode::network::{Network, NetworkError};
+
+    const AMPLIFIER: &str = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0";
+    const FEEDBACK_AMPLIFIER: &str = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+    // doubles every input until it reads a 0
+    const DOUBLER: &str = "3,20,1006,20,14,102,2,20,21,4,21,1105,1,0,99";
+
+    fn amplifiers(program: &str, phases: &[i128]) -> Vec<Automaton> {
+        phases.iter()
+            .enumerate()
+            .map(|(index, &phase)| {
+                let amplifier = Automaton::new_with_program(program).add_initial_input(phase);
+                if index == 0 { amplifier.add_initial_input(0) } else { amplifier }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let machines = Network::pipeline(amplifiers(AMPLIFIER, &[4, 3, 2, 1, 0])).run().unwrap();
+        assert!(machines.iter().all(Automaton::is_halted));
+        assert_eq!(machines[4].get_last_output(), 43210);
+    }
+
+    #[test]
+    fn test_ring() {
+        let machines = Network::ring(amplifiers(FEEDBACK_AMPLIFIER, &[9, 8, 7, 6, 5])).run().unwrap();
+        assert_eq!(machines[4].get_last_output(), 139629729);
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let source = Automaton::new_with_program("104,1,104,5,104,0,99");
+        let sinks = vec![Automaton::new_with_program(DOUBLER), Automaton::new_with_program(DOUBLER)];
+        let mut machines = Network::fan_out(source, sinks).run().unwrap();
+        assert_eq!(machines[1].get_output(2), Some(vec![2, 10]));
+        assert_eq!(machines[2].get_output(2), Some(vec![2, 10]));
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let mut network = Network::new();
+        let waiting = network.add(Automaton::new_with_program(DOUBLER));
+        let halting = network.add(Automaton::new_with_program("99"));
+        let other = network.add(Automaton::new_with_program(DOUBLER));
+        network.connect(waiting, other);
+        network.connect(other, waiting);
+        network.connect(halting, waiting);
+
+        assert_eq!(network.run().err(), Some(NetworkError::Deadlock { blocked: vec![0, 2] }));
+    }
+
+    #[test]
+    fn test_fault() {
+        let mut network = Network::pipeline(vec![
+            Automaton::new_with_program("104,42,99"),
+            Automaton::new_with_program("3,0,4,0,3,0,99"),
+        ]);
+        // writes the 42 it reads over its next instruction
+        network.add(Automaton::new_with_program("3,2,0"));
+        network.connect(0, 2);
+        let error = NetworkError::Fault { machine: 2, error: IntcodeError::InvalidOpcode { pc: 2, opcode: 42 } };
+        assert_eq!(network.run().err(), Some(error));
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use crate::intcode::Automaton;
+use crate::intcode::word::Word;
+
+/// A parsed Intcode program: comma separated integers, with any whitespace
+/// around them and `;` comments running to the end of the line.
+///
+/// Parse once with `str::parse`, then load it into as many machines as
+/// needed with `Automaton::from_program`; clones share the same words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program<W: Word = i128> {
+    words: Arc<[W]>,
+}
+
+/// A token that is not an integer, e.g. the empty token of `1,,2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseProgramError {
+    /// The index of the token, which is the address it would have been loaded at.
+    pub index: usize,
+    pub token: String,
+}
+
+impl fmt::Display for ParseProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid token {:?} at index {}", self.token, self.index)
+    }
+}
+
+impl std::error::Error for ParseProgramError {}
+
+impl<W: Word> FromStr for Program<W> {
+    type Err = ParseProgramError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code : String = s.lines()
+            .map(|line| line.split(';').next().unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if code.trim().is_empty() {
+            return Ok(Program::from(Vec::new()));
+        }
+
+        let words = code.split(',')
+            .map(str::trim)
+            .enumerate()
+            .map(|(index, token)| token.parse().map_err(|_| ParseProgramError { index, token: token.to_string() }))
+            .collect::<Result<Vec<W>, _>>()?;
+        Ok(Program::from(words))
+    }
+}
+
+impl<W: Word> From<Vec<W>> for Program<W> {
+    fn from(words: Vec<W>) -> Self {
+        Self { words: words.into() }
+    }
+}
+
+impl<W: Word> Program<W> {
+    pub fn words(&self) -> &[W] {
+        &self.words
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+impl<W: Word> Automaton<W> {
+    pub fn from_program(program: &Program<W>) -> Self {
+        Self::blank().load_program(program)
+    }
+
+    pub(crate) fn load_program(mut self, program: &Program<W>) -> Self {
+        for (address, word) in program.words().iter().enumerate() {
+            self.memory.write(address as u128, word.clone());
+        }
+        self.image = self.memory.fork();
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::{Automaton, RunState};
+    use crate::intcode::program::{ParseProgramError, Program};
+
+    #[test]
+    fn test_parse() {
+        let program : Program = "1, 9,10, 3,\n2,3,11,0, ; add and multiply\n99,\n30,40,50\n".parse().unwrap();
+        assert_eq!(program.words(), &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+
+        let program : Program<i64> = "; nothing here\n \n".parse().unwrap();
+        assert!(program.is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = "1,0,0,x0,99".parse::<Program>().err();
+        assert_eq!(error, Some(ParseProgramError { index: 3, token: String::from("x0") }));
+
+        let error = "1,0,,0,99".parse::<Program>().err();
+        assert_eq!(error, Some(ParseProgramError { index: 2, token: String::new() }));
+
+        let error = "1,0,0,0,99,".parse::<Program>().err();
+        assert_eq!(error, Some(ParseProgramError { index: 5, token: String::new() }));
+
+        let error = "104,1 2,99".parse::<Program>().err();
+        assert_eq!(error, Some(ParseProgramError { index: 1, token: String::from("1 2") }));
+
+        let error = "104,9223372036854775808,99".parse::<Program<i64>>().err();
+        assert_eq!(error.map(|error| error.index), Some(1));
+    }
+
+    #[test]
+    fn test_load_many() {
+        let program : Program = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+        for (input, output) in [(8, 1), (7, 0)] {
+            let mut automaton = Automaton::from_program(&program).add_initial_input(input);
+            assert_eq!(automaton.run(), Ok(RunState::Halted));
+            assert_eq!(automaton.get_last_output(), output);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::VecDeque;
+use crate::intcode::{Automaton, RunState};
+use crate::intcode::network::NetworkError;
+
+/// The order in which a `Scheduler` lets its machines execute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Every machine in turn executes one instruction.
+    RoundRobin,
+    /// Every machine in turn runs until it halts or waits for input.
+    UntilBlocked,
+    /// A machine picked by a pseudo-random generator executes one
+    /// instruction. The same seed always gives the same interleaving.
+    Random { seed: u64 },
+}
+
+/// Where the outputs of a machine go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// Every output value goes to the input of machine `to`.
+    Forward { to: usize },
+    /// Outputs are frames of `size` values, at least 2, of which the first is
+    /// the address of the machine that receives the other values.
+    Addressed { size: usize },
+}
+
+/// Values addressed to something that is not a machine of the scheduler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub destination: i128,
+    pub values: Vec<i128>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    AllHalted,
+    /// No machine can make progress without new input from outside.
+    Idle,
+    /// A packet is waiting to be taken with `take_undeliverable`.
+    Undeliverable,
+}
+
+/// A machine fed `empty_input` this many times in a row, without receiving
+/// or sending anything in between, is considered idle.
+const IDLE_READS: usize = 2;
+
+/// Interleaves many machines on the current thread, in an order fully
+/// determined by the `Policy`, and moves outputs to inputs by `Rule`s.
+/// Outputs of a machine without a rule stay in its output buffer. Like
+/// `Network`, it only runs machines with the default `i128` words.
+pub struct Scheduler {
+    machines: Vec<Automaton>,
+    rules: Vec<Option<Rule>>,
+    empty_reads: Vec<usize>,
+    policy: Policy,
+    empty_input: Option<i128>,
+    undeliverable: VecDeque<Packet>,
+    random: u64,
+}
+
+impl Scheduler {
+    pub fn new(policy: Policy) -> Self {
+        let random = match policy {
+            Policy::Random { seed } => seed.max(1),
+            _ => 1,
+        };
+        Self {
+            machines: Vec::new(),
+            rules: Vec::new(),
+            empty_reads: Vec::new(),
+            policy,
+            empty_input: None,
+            undeliverable: VecDeque::new(),
+            random,
+        }
+    }
+
+    /// Feeds `value` to a machine that waits for input with nothing queued,
+    /// instead of letting it block, like the -1 of the day 23 network.
+    pub fn with_empty_input(mut self, value: i128) -> Self {
+        self.empty_input = Some(value);
+
+        self
+    }
+
+    /// Adds a machine and returns its id, which is also its address.
+    pub fn add(&mut self, automaton: Automaton) -> usize {
+        self.machines.push(automaton);
+        self.rules.push(None);
+        self.empty_reads.push(0);
+        self.machines.len() - 1
+    }
+
+    /// Sends the outputs of machine `from` by `rule`, replacing any earlier
+    /// rule. Panics if `from` is not the id of a machine, or if an `Addressed`
+    /// frame is too small to hold an address and a value.
+    pub fn route(&mut self, from: usize, rule: Rule) {
+        assert!(from < self.machines.len(), "no machine {from} to route from");
+        if let Rule::Addressed { size } = rule {
+            assert!(size >= 2, "addressed frames need room for an address and a value, got size {size}");
+        }
+        self.rules[from] = Some(rule);
+    }
+
+    pub fn machine(&self, id: usize) -> &Automaton {
+        &self.machines[id]
+    }
+
+    pub fn machines(&self) -> &[Automaton] {
+        &self.machines
+    }
+
+    /// Queues values for machine `to`. Values for a halted machine are dropped.
+    pub fn send(&mut self, to: usize, values: &[i128]) {
+        self.deliver(to as i128, values);
+    }
+
+    pub fn take_undeliverable(&mut self) -> Vec<Packet> {
+        self.undeliverable.drain(..).collect()
+    }
+
+    /// Runs the machines until all have halted, the network is idle or a
+    /// packet could not be delivered.
+    pub fn run(&mut self) -> Result<Stop, NetworkError> {
+        loop {
+            if !self.undeliverable.is_empty() {
+                return Ok(Stop::Undeliverable);
+            }
+            if self.machines.iter().all(Automaton::is_halted) {
+                return Ok(Stop::AllHalted);
+            }
+            if self.is_idle() {
+                return Ok(Stop::Idle);
+            }
+
+            match self.policy {
+                Policy::RoundRobin | Policy::UntilBlocked => {
+                    for id in 0..self.machines.len() {
+                        self.tick(id)?;
+                    }
+                }
+                Policy::Random { .. } => {
+                    let id = (self.next_random() % self.machines.len() as u64) as usize;
+                    self.tick(id)?;
+                }
+            }
+        }
+    }
+
+    fn tick(&mut self, id: usize) -> Result<(), NetworkError> {
+        let fault = |error| NetworkError::Fault { machine: id, error };
+        let machine = &mut self.machines[id];
+        if machine.is_halted() {
+            return Ok(());
+        }
+        if machine.state() == RunState::AwaitingInput && machine.input.is_empty() {
+            match self.empty_input {
+                Some(value) => {
+                    machine.runtime_input(value).map_err(fault)?;
+                    self.empty_reads[id] += 1;
+                }
+                None => return Ok(()),
+            }
+        }
+
+        match self.policy {
+            Policy::UntilBlocked => machine.run(),
+            Policy::RoundRobin | Policy::Random { .. } => machine.step(),
+        }.map_err(fault)?;
+
+        let size = match self.rules[id] {
+            None => return Ok(()),
+            Some(Rule::Forward { .. }) => 1,
+            Some(Rule::Addressed { size }) => size,
+        };
+        while let Some(frame) = self.machines[id].get_output(size) {
+            self.empty_reads[id] = 0;
+            match self.rules[id] {
+                Some(Rule::Forward { to }) => self.deliver(to as i128, &frame),
+                _ => self.deliver(frame[0], &frame[1..]),
+            }
+        }
+        Ok(())
+    }
+
+    fn deliver(&mut self, destination: i128, values: &[i128]) {
+        match usize::try_from(destination).ok().and_then(|id| self.machines.get_mut(id).map(|machine| (id, machine))) {
+            Some((id, machine)) => {
+                for &value in values {
+                    if machine.runtime_input(value).is_err() {
+                        return;
+                    }
+                }
+                self.empty_reads[id] = 0;
+            }
+            None => self.undeliverable.push_back(Packet { destination, values: values.to_vec() }),
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.machines.iter()
+            .zip(&self.empty_reads)
+            .all(|(machine, &empty_reads)| machine.is_halted() || machine.input.is_empty() && match self.empty_input {
+                Some(_) => empty_reads >= IDLE_READS,
+                None => machine.state() == RunState::AwaitingInput,
+            })
+    }
+
+    // xorshift64
+    fn next_random(&mut self) -> u64 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        self.random
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::{Automaton, IntcodeError};
+    use crate::intcode::network::NetworkError;
+    use crate::intcode::scheduler::{Packet, Policy, Rule, Scheduler, Stop};
+
+    const FEEDBACK_AMPLIFIER: &str = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+    // reads (address, value) pairs, skipping negative addresses, and sends the
+    // pair (7, value + 1) to the address, forever
+    const RELAY: &str = "3,100,1007,100,0,102,1005,102,0,3,101,1001,101,1,101,4,100,104,7,4,101,1105,1,0";
+
+    fn feedback_loop(policy: Policy) -> i128 {
+        let mut scheduler = Scheduler::new(policy);
+        for (id, phase) in [9, 8, 7, 6, 5].into_iter().enumerate() {
+            scheduler.add(Automaton::new_with_program(FEEDBACK_AMPLIFIER).add_initial_input(phase));
+            scheduler.route(id, Rule::Forward { to: (id + 1) % 5 });
+        }
+        scheduler.send(0, &[0]);
+
+        assert_eq!(scheduler.run(), Ok(Stop::AllHalted));
+        scheduler.machine(4).get_last_output()
+    }
+
+    #[test]
+    fn test_policies_agree_on_feedback_loop() {
+        assert_eq!(feedback_loop(Policy::UntilBlocked), 139629729);
+        assert_eq!(feedback_loop(Policy::RoundRobin), 139629729);
+        assert_eq!(feedback_loop(Policy::Random { seed: 7 }), 139629729);
+        assert_eq!(feedback_loop(Policy::Random { seed: 0 }), 139629729);
+    }
+
+    #[test]
+    fn test_addressed_routing_and_idle() {
+        let mut scheduler = Scheduler::new(Policy::RoundRobin).with_empty_input(-1);
+        for id in 0..2 {
+            scheduler.add(Automaton::new_with_program(RELAY));
+            scheduler.route(id, Rule::Addressed { size: 3 });
+        }
+        assert_eq!(scheduler.run(), Ok(Stop::Idle));
+
+        // hop from machine 0 to machine 1, then leave the network
+        scheduler.send(0, &[1, 10]);
+        assert_eq!(scheduler.run(), Ok(Stop::Undeliverable));
+        assert_eq!(scheduler.take_undeliverable(), vec![Packet { destination: 7, values: vec![7, 12] }]);
+        assert_eq!(scheduler.run(), Ok(Stop::Idle));
+    }
+
+    #[test]
+    #[should_panic(expected = "addressed frames need room for an address and a value")]
+    fn test_addressed_frames_need_a_value() {
+        let mut scheduler = Scheduler::new(Policy::RoundRobin);
+        let id = scheduler.add(Automaton::new_with_program(RELAY));
+        scheduler.route(id, Rule::Addressed { size: 0 });
+    }
+
+    #[test]
+    #[should_panic(expected = "no machine 1 to route from")]
+    fn test_route_from_unknown_machine() {
+        let mut scheduler = Scheduler::new(Policy::RoundRobin);
+        scheduler.add(Automaton::new_with_program(RELAY));
+        scheduler.route(1, Rule::Forward { to: 0 });
+    }
+
+    #[test]
+    fn test_blocked_machines_are_idle() {
+        let mut scheduler = Scheduler::new(Policy::UntilBlocked);
+        scheduler.add(Automaton::new_with_program("3,0,99"));
+        scheduler.add(Automaton::new_with_program("99"));
+        assert_eq!(scheduler.run(), Ok(Stop::Idle));
+
+        scheduler.send(0, &[99]);
+        assert_eq!(scheduler.run(), Ok(Stop::AllHalted));
+    }
+
+    #[test]
+    fn test_fault() {
+        let mut scheduler = Scheduler::new(Policy::RoundRobin);
+        scheduler.add(Automaton::new_with_program("99"));
+        scheduler.add(Automaton::new_with_program("1,0,0,0,42"));
+        assert_eq!(scheduler.run(), Err(NetworkError::Fault { machine: 1, error: IntcodeError::InvalidOpcode { pc: 4, opcode: 42 } }));
+    }
+}
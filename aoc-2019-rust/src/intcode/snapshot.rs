@@ -0,0 +1,398 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use crate::intcode::{Automaton, IntcodeError, RunState};
+use crate::intcode::memory::Memory;
+use crate::intcode::word::{FixedWord, Overflow};
+use crate::intcode::varint::{read_signed, read_unsigned, write_signed, write_unsigned};
+
+const MAGIC: &[u8; 4] = b"ICSN";
+const VERSION: u8 = 1;
+
+const STATES: [RunState; 7] = [
+    RunState::Running,
+    RunState::AwaitingInput,
+    RunState::Halted,
+    RunState::Faulted,
+    RunState::StepLimitReached,
+    RunState::Breakpoint,
+    RunState::Overflowed,
+];
+
+const OVERFLOWS: [Overflow; 3] = [Overflow::Wrap, Overflow::Fault, Overflow::Promote];
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_state(out: &mut impl Write, state: RunState) -> io::Result<()> {
+    let state = STATES.iter().position(|&known| known == state).unwrap_or_default();
+    out.write_all(&[state as u8])
+}
+
+fn read_state(input: &mut impl Read) -> io::Result<RunState> {
+    let mut byte = [0];
+    input.read_exact(&mut byte)?;
+    STATES.get(byte[0] as usize).copied().ok_or(invalid_data("unknown run state"))
+}
+
+fn read_word<W: FixedWord>(input: &mut impl Read) -> io::Result<W> {
+    W::try_from(read_signed(input)?).map_err(|_| invalid_data("value does not fit the word type"))
+}
+
+fn read_usize(input: &mut impl Read) -> io::Result<usize> {
+    usize::try_from(read_unsigned(input)?).map_err(|_| invalid_data("value out of range"))
+}
+
+/// A variant byte, then the pc and the other fields in declaration order.
+fn write_error(out: &mut impl Write, error: &IntcodeError) -> io::Result<()> {
+    match *error {
+        IntcodeError::InvalidOpcode { pc, opcode } => {
+            out.write_all(&[0])?;
+            write_unsigned(out, pc)?;
+            write_signed(out, opcode)
+        }
+        IntcodeError::InvalidParameterMode { pc, mode } => {
+            out.write_all(&[1])?;
+            write_unsigned(out, pc)?;
+            write_signed(out, mode)
+        }
+        IntcodeError::WriteToImmediate { pc } => {
+            out.write_all(&[2])?;
+            write_unsigned(out, pc)
+        }
+        IntcodeError::NegativeAddress { pc, address } => {
+            out.write_all(&[3])?;
+            write_unsigned(out, pc)?;
+            write_signed(out, address)
+        }
+        IntcodeError::MissingOperand { pc, index } => {
+            out.write_all(&[4])?;
+            write_unsigned(out, pc)?;
+            write_unsigned(out, index as u128)
+        }
+        IntcodeError::IllegalTransition { pc, state } => {
+            out.write_all(&[5])?;
+            write_unsigned(out, pc)?;
+            write_state(out, state)
+        }
+        IntcodeError::IncompleteFrame { pc, size, found } => {
+            out.write_all(&[6])?;
+            write_unsigned(out, pc)?;
+            write_unsigned(out, size as u128)?;
+            write_unsigned(out, found as u128)
+        }
+        IntcodeError::AddressOutOfRange { pc, address, limit } => {
+            out.write_all(&[7])?;
+            write_unsigned(out, pc)?;
+            write_unsigned(out, address)?;
+            write_unsigned(out, limit)
+        }
+        IntcodeError::WriteToProtected { pc, address } => {
+            out.write_all(&[8])?;
+            write_unsigned(out, pc)?;
+            write_unsigned(out, address)
+        }
+        IntcodeError::Overflow { pc } => {
+            out.write_all(&[9])?;
+            write_unsigned(out, pc)
+        }
+        IntcodeError::RelativeBaseOverflow { pc } => {
+            out.write_all(&[10])?;
+            write_unsigned(out, pc)
+        }
+        IntcodeError::WordOutOfRange { pc } => {
+            out.write_all(&[11])?;
+            write_unsigned(out, pc)
+        }
+    }
+}
+
+fn read_error(input: &mut impl Read) -> io::Result<IntcodeError> {
+    let mut variant = [0];
+    input.read_exact(&mut variant)?;
+    let pc = read_unsigned(input)?;
+    Ok(match variant[0] {
+        0 => IntcodeError::InvalidOpcode { pc, opcode: read_signed(input)? },
+        1 => IntcodeError::InvalidParameterMode { pc, mode: read_signed(input)? },
+        2 => IntcodeError::WriteToImmediate { pc },
+        3 => IntcodeError::NegativeAddress { pc, address: read_signed(input)? },
+        4 => IntcodeError::MissingOperand { pc, index: read_usize(input)? },
+        5 => IntcodeError::IllegalTransition { pc, state: read_state(input)? },
+        6 => IntcodeError::IncompleteFrame { pc, size: read_usize(input)?, found: read_usize(input)? },
+        7 => IntcodeError::AddressOutOfRange { pc, address: read_unsigned(input)?, limit: read_unsigned(input)? },
+        8 => IntcodeError::WriteToProtected { pc, address: read_unsigned(input)? },
+        9 => IntcodeError::Overflow { pc },
+        10 => IntcodeError::RelativeBaseOverflow { pc },
+        11 => IntcodeError::WordOutOfRange { pc },
+        _ => return Err(invalid_data("unknown error")),
+    })
+}
+
+fn write_memory<W: FixedWord>(out: &mut impl Write, memory: &dyn Memory<W>) -> io::Result<()> {
+    write_unsigned(out, memory.len())?;
+    let cells = memory.cells();
+    write_unsigned(out, cells.len() as u128)?;
+    for (address, value) in cells {
+        write_unsigned(out, address)?;
+        write_signed(out, value.into())?;
+    }
+    Ok(())
+}
+
+fn read_memory<W: FixedWord>(input: &mut impl Read, memory: &mut dyn Memory<W>) -> io::Result<()> {
+    let len = read_unsigned(input)?;
+    if len > 0 {
+        memory.write(len - 1, W::ZERO);
+    }
+    for _ in 0..read_unsigned(input)? {
+        let address = read_unsigned(input)?;
+        memory.write(address, read_word(input)?);
+    }
+    Ok(())
+}
+
+impl<W: FixedWord> Automaton<W> {
+    /// Writes the complete machine state: run state, pc, relative base, the
+    /// instruction count, the overflow policy, protected program and memory
+    /// limit, memory, the image `reset` goes back to, pending input, the output
+    /// buffer, `output_curr_index` and the error of a faulted machine.
+    ///
+    /// The format is `ICSN`, a version byte, and then those fields as LEB128
+    /// varints; memory and image are stored as their length followed by the
+    /// non-zero cells, and the memory limit and fault as a byte saying whether
+    /// there is one.
+    /// Breakpoints, watchpoints and traces are debugging aids and not saved.
+    pub fn save_snapshot(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+
+        write_state(out, self.state)?;
+        write_unsigned(out, self.pc)?;
+        write_signed(out, self.relative_base)?;
+        write_unsigned(out, self.instructions as u128)?;
+
+        let overflow = OVERFLOWS.iter().position(|&overflow| overflow == self.overflow).unwrap_or_default();
+        out.write_all(&[overflow as u8])?;
+        write_unsigned(out, self.protected)?;
+        match self.memory_limit {
+            Some(limit) => {
+                out.write_all(&[1])?;
+                write_unsigned(out, limit)?;
+            }
+            None => out.write_all(&[0])?,
+        }
+
+        write_memory(out, self.memory.as_ref())?;
+        write_memory(out, self.image.as_ref())?;
+
+        for buffer in [&self.input, &self.output] {
+            write_unsigned(out, buffer.len() as u128)?;
+            for &value in buffer {
+                write_signed(out, value.into())?;
+            }
+        }
+        write_unsigned(out, self.output_curr_index as u128)?;
+
+        match &self.fault {
+            Some(error) => {
+                out.write_all(&[1])?;
+                write_error(out, error)
+            }
+            None => out.write_all(&[0]),
+        }
+    }
+
+    /// Restores a machine written by `save_snapshot` into the default memory
+    /// backend. A machine saved while faulted keeps reporting its error. Values
+    /// are checked against the word type, which need not be the one saved.
+    pub fn load_snapshot(input: &mut impl Read) -> io::Result<Automaton<W>> {
+        let mut header = [0; 5];
+        input.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid_data("not an Intcode snapshot"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid_data("unsupported Intcode snapshot version"));
+        }
+
+        let mut automaton = Automaton::blank();
+        automaton.state = read_state(input)?;
+        automaton.pc = read_unsigned(input)?;
+        automaton.relative_base = read_signed(input)?;
+        automaton.instructions = u64::try_from(read_unsigned(input)?)
+            .map_err(|_| invalid_data("instruction count out of range"))?;
+
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        automaton.overflow = *OVERFLOWS.get(byte[0] as usize).ok_or(invalid_data("unknown overflow policy"))?;
+        automaton.protected = read_unsigned(input)?;
+        input.read_exact(&mut byte)?;
+        automaton.memory_limit = match byte[0] {
+            0 => None,
+            1 => Some(read_unsigned(input)?),
+            _ => return Err(invalid_data("invalid memory limit")),
+        };
+
+        read_memory(input, automaton.memory.as_mut())?;
+        read_memory(input, automaton.image.as_mut())?;
+
+        for buffer in [&mut automaton.input, &mut automaton.output] {
+            for _ in 0..read_unsigned(input)? {
+                buffer.push_back(read_word(input)?);
+            }
+        }
+        automaton.output_curr_index = read_usize(input)?;
+
+        input.read_exact(&mut byte)?;
+        automaton.fault = match byte[0] {
+            0 => None,
+            1 => Some(read_error(input)?),
+            _ => return Err(invalid_data("invalid fault")),
+        };
+
+        Ok(automaton)
+    }
+
+    pub fn save_snapshot_to(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.save_snapshot(&mut out)?;
+        out.flush()
+    }
+
+    pub fn load_snapshot_from(path: impl AsRef<Path>) -> io::Result<Automaton<W>> {
+        Automaton::load_snapshot(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use crate::intcode::{Automaton, IntcodeError, RunState};
+    use crate::intcode::word::Overflow;
+
+    // echoes inputs until it reads a 0, keeping a running total on the relative base
+    const PROGRAM: &str = "3,100,9,100,4,100,1005,100,0,204,0,99";
+
+    fn round_trip(automaton: &Automaton) -> Automaton {
+        let mut out = Vec::new();
+        automaton.save_snapshot(&mut out).unwrap();
+        Automaton::load_snapshot(&mut out.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_resume_from_snapshot() {
+        let mut original = Automaton::new_with_program(PROGRAM)
+            .add_initial_input(5)
+            .add_initial_input(1000);
+        original.runtime_input(-2).unwrap();
+        original.run().unwrap();
+        original.runtime_input(7).unwrap();
+        assert_eq!(original.get_output(1), Some(vec![5]));
+
+        let mut restored = round_trip(&original);
+        assert_eq!(restored.state(), RunState::AwaitingInput);
+        assert_eq!(restored.pc, original.pc);
+        assert_eq!(restored.relative_base, original.relative_base);
+        assert_eq!(restored.input, original.input);
+        assert_eq!(restored.output_curr_index, 1);
+        assert_eq!(restored.dump_memory(), original.dump_memory());
+
+        for automaton in [&mut original, &mut restored] {
+            automaton.runtime_input(0).unwrap();
+            assert_eq!(automaton.run(), Ok(RunState::Halted));
+        }
+        assert_eq!(restored.output, original.output);
+        assert_eq!(restored.get_output(4), Some(vec![1000, -2, 7, 0]));
+    }
+
+    #[test]
+    fn test_reset_after_restore() {
+        let mut automaton = Automaton::new_with_program(PROGRAM).with_patch(7, 1000).add_initial_input(5);
+        automaton.run().unwrap();
+        assert_eq!(automaton.instructions_executed(), 6);
+
+        let mut restored = round_trip(&automaton);
+        assert_eq!(restored.instructions_executed(), 6);
+        restored.reset();
+        assert_eq!(restored.dump_memory(), Automaton::new_with_program(PROGRAM).with_patch(7, 1000).dump_memory());
+    }
+
+    #[test]
+    fn test_settings_are_restored() {
+        let automaton = Automaton::new_with_program("1101,1,2,3,99")
+            .with_protected_program()
+            .with_memory_limit(16)
+            .with_overflow(Overflow::Wrap);
+
+        let mut restored = round_trip(&automaton);
+        assert_eq!(restored.run(), Err(IntcodeError::WriteToProtected { pc: 0, address: 3 }));
+        let mut restored = round_trip(&automaton.with_patch(3, 20));
+        assert_eq!(restored.run(), Err(IntcodeError::AddressOutOfRange { pc: 0, address: 20, limit: 16 }));
+
+        let automaton = Automaton::new_with_program(&format!("1101,{},1,5,99", i128::MAX)).with_overflow(Overflow::Wrap);
+        let mut restored = round_trip(&automaton);
+        assert_eq!(restored.run(), Ok(RunState::Halted));
+        assert_eq!(restored.peek(5), i128::MIN);
+    }
+
+    #[test]
+    fn test_faulted_machine_stays_faulted() {
+        let mut automaton = Automaton::new_with_program("42");
+        assert_eq!(automaton.run(), Err(IntcodeError::InvalidOpcode { pc: 0, opcode: 42 }));
+
+        let mut restored = round_trip(&automaton);
+        assert_eq!(restored.state(), RunState::Faulted);
+        assert_eq!(restored.run(), Err(IntcodeError::InvalidOpcode { pc: 0, opcode: 42 }));
+
+        let mut automaton = Automaton::new_with_program("1101,1,2,20,99").with_memory_limit(16);
+        let error = IntcodeError::AddressOutOfRange { pc: 0, address: 20, limit: 16 };
+        assert_eq!(automaton.run(), Err(error.clone()));
+        assert_eq!(round_trip(&automaton).run(), Err(error));
+    }
+
+    #[test]
+    fn test_word_types() {
+        let mut automaton = Automaton::<i64>::new_with_words(PROGRAM).add_initial_input(5);
+        automaton.run().unwrap();
+        let mut out = Vec::new();
+        automaton.save_snapshot(&mut out).unwrap();
+        let mut restored = Automaton::<i64>::load_snapshot(&mut out.as_slice()).unwrap();
+        restored.runtime_input(0).unwrap();
+        assert_eq!(restored.run(), Ok(RunState::Halted));
+        assert_eq!(restored.get_output(3), Some(vec![5, 0, 100]));
+
+        let mut out = Vec::new();
+        Automaton::new().add_initial_input(i128::MAX).save_snapshot(&mut out).unwrap();
+        let error = Automaton::<i64>::load_snapshot(&mut out.as_slice()).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_rejects_other_data() {
+        let error = Automaton::<i128>::load_snapshot(&mut &b"ICTR\x01\x00"[..]).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let error = Automaton::<i128>::load_snapshot(&mut &b"ICSN\x09\x00"[..]).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let mut out = Vec::new();
+        Automaton::new_with_program(PROGRAM).save_snapshot(&mut out).unwrap();
+        out.truncate(out.len() - 3);
+        let error = Automaton::<i128>::load_snapshot(&mut out.as_slice()).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_snapshot_file() {
+        let path = std::env::temp_dir().join(format!("intcode-snapshot-{}.bin", std::process::id()));
+        let mut automaton = Automaton::new_with_program(PROGRAM);
+        automaton.run().unwrap();
+        automaton.save_snapshot_to(&path).unwrap();
+
+        let mut restored : Automaton = Automaton::load_snapshot_from(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        restored.runtime_input(0).unwrap();
+        assert_eq!(restored.run(), Ok(RunState::Halted));
+    }
+}
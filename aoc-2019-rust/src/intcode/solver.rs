@@ -0,0 +1,169 @@
+use std::{fmt, iter};
+use std::ops::RangeInclusive;
+use itertools::Itertools;
+use crate::intcode::{Automaton, IntcodeError};
+
+/// Finds values for input cells that make a program leave `target` at an
+/// output address, for programs whose output is a linear function of the
+/// inputs, like `memory[0]` in terms of noun and verb in day 2.
+///
+/// Instead of running every combination, it runs the program once with all
+/// inputs 0 and once per input set to 1 to learn the coefficients, then
+/// solves for the target and confirms the answer with one more run.
+pub struct LinearSolver {
+    automaton: Automaton,
+    inputs: Vec<(u128, RangeInclusive<i128>)>,
+    output: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    /// The program faulted while probing with the given input values.
+    Fault { values: Vec<i128>, error: IntcodeError },
+    /// The output at the given input values is not what the coefficients predict.
+    NotLinear { values: Vec<i128> },
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::Fault { values, error } => write!(f, "program faulted for inputs {values:?}: {error}"),
+            SolveError::NotLinear { values } => write!(f, "output is not linear in the inputs, see inputs {values:?}"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+impl LinearSolver {
+    /// `automaton` must not have run yet; it is reset before every run.
+    pub fn new(automaton: Automaton, output: u128) -> Self {
+        Self {
+            automaton,
+            inputs: Vec::new(),
+            output,
+        }
+    }
+
+    /// Adds the cell at `address` as an input taking values from `range`.
+    pub fn with_input(mut self, address: u128, range: RangeInclusive<i128>) -> Self {
+        self.inputs.push((address, range));
+
+        self
+    }
+
+    /// The first input values, in the order the inputs were added and
+    /// counting up, for which the output is `target`. `None` if there are none.
+    pub fn solve(&mut self, target: i128) -> Result<Option<Vec<i128>>, SolveError> {
+        if self.inputs.is_empty() {
+            return Ok(None);
+        }
+        let constant = self.evaluate(&vec![0; self.inputs.len()])?;
+        let coefficients = (0..self.inputs.len())
+            .map(|index| {
+                let mut values = vec![0; self.inputs.len()];
+                values[index] = 1;
+                self.evaluate(&values)?.checked_sub(constant).ok_or(SolveError::NotLinear { values })
+            })
+            .collect::<Result<Vec<i128>, SolveError>>()?;
+
+        let ends : Vec<i128> = self.inputs.iter().map(|(_, range)| *range.end()).collect();
+        self.expect_linear(&ends, constant, &coefficients)?;
+
+        let ranges : Vec<RangeInclusive<i128>> = self.inputs.iter().map(|(_, range)| range.clone()).collect();
+        let solution = solve_linear_equation(constant, &coefficients, &ranges, target);
+
+        if let Some(values) = &solution {
+            self.expect_linear(values, constant, &coefficients)?;
+        }
+        Ok(solution)
+    }
+
+    fn expect_linear(&mut self, values: &[i128], constant: i128, coefficients: &[i128]) -> Result<(), SolveError> {
+        let predicted = linear_value(constant, coefficients, values);
+        if Some(self.evaluate(values)?) == predicted {
+            Ok(())
+        } else {
+            Err(SolveError::NotLinear { values: values.to_vec() })
+        }
+    }
+
+    fn evaluate(&mut self, values: &[i128]) -> Result<i128, SolveError> {
+        self.automaton.reset();
+        for ((address, _), &value) in self.inputs.iter().zip(values) {
+            self.automaton.poke(*address, value);
+        }
+        self.automaton.run()
+            .map_err(|error| SolveError::Fault { values: values.to_vec(), error })?;
+        Ok(self.automaton.peek(self.output))
+    }
+}
+
+/// `constant + sum(coefficients[i] * values[i])`, `None` if it overflows.
+pub(crate) fn linear_value(constant: i128, coefficients: &[i128], values: &[i128]) -> Option<i128> {
+    values.iter()
+        .zip(coefficients)
+        .try_fold(constant, |sum, (value, coefficient)| sum.checked_add(value.checked_mul(*coefficient)?))
+}
+
+/// The first `values`, counting up within `ranges`, for which
+/// `constant + sum(coefficients[i] * values[i]) == target`. Only the last
+/// value is solved for, the others are tried in turn.
+pub(crate) fn solve_linear_equation(constant: i128, coefficients: &[i128], ranges: &[RangeInclusive<i128>], target: i128) -> Option<Vec<i128>> {
+    let ((last_coefficient, others), (last_range, other_ranges)) = (coefficients.split_last()?, ranges.split_last()?);
+    // itertools yields nothing, not one empty product, for no ranges
+    let mut prefixes : Box<dyn Iterator<Item = Vec<i128>>> = if other_ranges.is_empty() {
+        Box::new(iter::once(Vec::new()))
+    } else {
+        Box::new(other_ranges.iter().cloned().multi_cartesian_product())
+    };
+    prefixes.find_map(|mut values| {
+        let rest = target.checked_sub(linear_value(constant, others, &values)?)?;
+        let last = match *last_coefficient {
+            0 if rest == 0 => *last_range.start(),
+            0 => return None,
+            coefficient if rest.checked_rem(coefficient)? != 0 => return None,
+            coefficient => rest.checked_div(coefficient)?,
+        };
+        last_range.contains(&last).then(|| {
+            values.push(last);
+            values
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::Automaton;
+    use crate::intcode::solver::{LinearSolver, SolveError};
+
+    // memory[0] = 3 * memory[20] + memory[21] + 7
+    const LINEAR: &str = "1002,20,3,22,1,22,21,22,1001,22,7,0,99,0,0,0,0,0,0,0,0,0,0";
+
+    fn solver(program: &str) -> LinearSolver {
+        LinearSolver::new(Automaton::new_with_program(program), 0)
+            .with_input(20, 0..=99)
+            .with_input(21, 0..=99)
+    }
+
+    #[test]
+    fn test_solve() {
+        assert_eq!(solver(LINEAR).solve(100), Ok(Some(vec![0, 93])));
+        assert_eq!(solver(LINEAR).solve(403), Ok(Some(vec![99, 99])));
+        assert_eq!(solver(LINEAR).solve(404), Ok(None));
+    }
+
+    #[test]
+    fn test_single_input() {
+        let mut solver = LinearSolver::new(Automaton::new_with_program(LINEAR), 0).with_input(21, -10..=10);
+        assert_eq!(solver.solve(0), Ok(Some(vec![-7])));
+        assert_eq!(solver.solve(1), Ok(Some(vec![-6])));
+    }
+
+    #[test]
+    fn test_not_linear() {
+        // memory[0] = memory[20] * memory[21]
+        let program = "2,20,21,0,99,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0";
+        assert_eq!(solver(program).solve(42), Err(SolveError::NotLinear { values: vec![99, 99] }));
+    }
+}
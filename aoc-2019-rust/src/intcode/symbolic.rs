@@ -0,0 +1,452 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::ops::{self, RangeInclusive};
+use crate::intcode::{decode_value, Automaton, Instruction, InstructionDef, IntcodeError, Parameter, ParameterMode, RunState};
+use crate::intcode::solver::solve_linear_equation;
+use crate::intcode::word::FixedWord;
+
+/// A value computed from symbols, the unknown inputs of a `Symbolic` run.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Const(i128),
+    Symbol(String),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    LessThan(Box<Expr>, Box<Expr>),
+    Equals(Box<Expr>, Box<Expr>),
+    /// The memory cell at an address that depends on symbols, as it was when
+    /// the program read it. `eval` cannot tell its value.
+    Load(Box<Expr>),
+}
+
+/// `constant + sum(coefficient * symbol)`, the normal form of an `Expr` that
+/// only adds symbols and multiplies them by constants.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Linear {
+    pub constant: i128,
+    pub terms: BTreeMap<String, i128>,
+}
+
+impl Expr {
+    pub fn symbol(name: &str) -> Expr {
+        Expr::Symbol(name.to_string())
+    }
+
+    // Equal `Load`s may still differ in value, the memory can change
+    // between the reads, so only expressions without them are compared.
+    pub fn less_than(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a < b) as i128),
+            (a, b) if a == b && !a.has_load() => Expr::Const(0),
+            (a, b) => Expr::LessThan(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn equals(self, other: Expr) -> Expr {
+        match (self, other) {
+            (a, b) if a == b && !a.has_load() => Expr::Const(1),
+            (Expr::Const(_), Expr::Const(_)) => Expr::Const(0),
+            (a, b) => Expr::Equals(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn has_load(&self) -> bool {
+        match self {
+            Expr::Const(_) | Expr::Symbol(_) => false,
+            Expr::Add(a, b) | Expr::Mul(a, b) | Expr::LessThan(a, b) | Expr::Equals(a, b) => a.has_load() || b.has_load(),
+            Expr::Load(_) => true,
+        }
+    }
+
+    pub fn as_const(&self) -> Option<i128> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value for the given symbol values, if all symbols are given and
+    /// the expression does not `Load` from a symbolic address.
+    pub fn eval(&self, values: &HashMap<String, i128>) -> Option<i128> {
+        Some(match self {
+            Expr::Const(value) => *value,
+            Expr::Symbol(name) => *values.get(name)?,
+            Expr::Add(a, b) => a.eval(values)?.checked_add(b.eval(values)?)?,
+            Expr::Mul(a, b) => a.eval(values)?.checked_mul(b.eval(values)?)?,
+            Expr::LessThan(a, b) => (a.eval(values)? < b.eval(values)?) as i128,
+            Expr::Equals(a, b) => (a.eval(values)? == b.eval(values)?) as i128,
+            Expr::Load(_) => return None,
+        })
+    }
+
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(value) => Some(Linear { constant: *value, ..Linear::default() }),
+            Expr::Symbol(name) => Some(Linear { constant: 0, terms: BTreeMap::from([(name.clone(), 1)]) }),
+            Expr::Add(a, b) => a.linear()?.plus(&b.linear()?, 1),
+            Expr::Mul(a, b) => match (a.linear()?, b.linear()?) {
+                (factor, linear) | (linear, factor) if factor.terms.is_empty() => Linear::default().plus(&linear, factor.constant),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl ops::Add for Expr {
+    type Output = Expr;
+
+    fn add(self, other: Expr) -> Expr {
+        match (self.linear(), other.linear()) {
+            (Some(a), Some(b)) => match a.plus(&b, 1) {
+                Some(sum) => sum.to_expr(),
+                None => Expr::Add(Box::new(self), Box::new(other)),
+            },
+            _ => Expr::Add(Box::new(self), Box::new(other)),
+        }
+    }
+}
+
+impl ops::Mul for Expr {
+    type Output = Expr;
+
+    fn mul(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::Const(a), Expr::Const(b)) if a.checked_mul(b).is_some() => Expr::Const(a * b),
+            (Expr::Const(0), _) | (_, Expr::Const(0)) => Expr::Const(0),
+            (Expr::Const(factor), expr) | (expr, Expr::Const(factor)) => match expr.linear().and_then(|linear| Linear::default().plus(&linear, factor)) {
+                Some(product) => product.to_expr(),
+                None => Expr::Mul(Box::new(Expr::Const(factor)), Box::new(expr)),
+            },
+            (a, b) => Expr::Mul(Box::new(a), Box::new(b)),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "{value}"),
+            Expr::Symbol(name) => write!(f, "{name}"),
+            Expr::Add(a, b) => write!(f, "{a} + {b}"),
+            Expr::Mul(a, b) => {
+                let factor = |expr: &Expr| match expr {
+                    Expr::Add(..) => format!("({expr})"),
+                    _ => expr.to_string(),
+                };
+                write!(f, "{} * {}", factor(a), factor(b))
+            }
+            Expr::LessThan(a, b) => write!(f, "({a} < {b})"),
+            Expr::Equals(a, b) => write!(f, "({a} == {b})"),
+            Expr::Load(address) => write!(f, "[{address}]"),
+        }
+    }
+}
+
+impl Linear {
+    /// `self + factor * other`, dropping terms that cancel out. `None` if a
+    /// coefficient overflows.
+    fn plus(mut self, other: &Linear, factor: i128) -> Option<Linear> {
+        self.constant = self.constant.checked_add(factor.checked_mul(other.constant)?)?;
+        for (name, coefficient) in &other.terms {
+            let term = self.terms.entry(name.clone()).or_default();
+            *term = term.checked_add(factor.checked_mul(*coefficient)?)?;
+        }
+        self.terms.retain(|_, coefficient| *coefficient != 0);
+        Some(self)
+    }
+
+    pub fn to_expr(&self) -> Expr {
+        let terms = self.terms.iter().map(|(name, &coefficient)| match coefficient {
+            1 => Expr::symbol(name),
+            _ => Expr::Mul(Box::new(Expr::Const(coefficient)), Box::new(Expr::symbol(name))),
+        });
+        let constant = (self.constant != 0 || self.terms.is_empty()).then_some(Expr::Const(self.constant));
+        terms.chain(constant)
+            .reduce(|sum, term| Expr::Add(Box::new(sum), Box::new(term)))
+            .unwrap_or(Expr::Const(0))
+    }
+
+    /// The first values of the symbols in `ranges`, in their order and
+    /// counting up, for which this is `target`. Symbols not in `ranges` must
+    /// not occur.
+    pub fn solve(&self, target: i128, ranges: &[(&str, RangeInclusive<i128>)]) -> Option<Vec<i128>> {
+        if self.terms.keys().any(|name| !ranges.iter().any(|(symbol, _)| symbol == name)) {
+            return None;
+        }
+        let coefficients : Vec<i128> = ranges.iter()
+            .map(|(symbol, _)| self.terms.get(*symbol).copied().unwrap_or_default())
+            .collect();
+        let ranges : Vec<RangeInclusive<i128>> = ranges.iter().map(|(_, range)| range.clone()).collect();
+        solve_linear_equation(self.constant, &coefficients, &ranges, target)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    Fault(IntcodeError),
+    /// A jump whose condition depends on symbols.
+    SymbolicBranch { pc: u128, condition: Expr },
+    /// A jump target, write address or relative base that depends on symbols.
+    SymbolicAddress { pc: u128, address: Expr },
+    /// The instruction at `pc` depends on symbols.
+    SymbolicInstruction { pc: u128, value: Expr },
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolicError::Fault(error) => write!(f, "{error}"),
+            SymbolicError::SymbolicBranch { pc, condition } => write!(f, "branch on {condition} at pc {pc}"),
+            SymbolicError::SymbolicAddress { pc, address } => write!(f, "symbolic address {address} at pc {pc}"),
+            SymbolicError::SymbolicInstruction { pc, value } => write!(f, "symbolic instruction {value} at pc {pc}"),
+        }
+    }
+}
+
+impl std::error::Error for SymbolicError {}
+
+impl From<IntcodeError> for SymbolicError {
+    fn from(error: IntcodeError) -> Self {
+        SymbolicError::Fault(error)
+    }
+}
+
+/// Runs a program with some memory cells or inputs replaced by symbols, so
+/// that memory and outputs become expressions of them, e.g. to solve day 2
+/// for `memory[0] == 19690720` algebraically:
+///
+/// ```text
+/// let mut symbolic = Symbolic::new(&automaton).with_symbol(1, "noun").with_symbol(2, "verb");
+/// symbolic.run()?;
+/// symbolic.value(0).linear()?.solve(19690720, &[("noun", 0..=99), ("verb", 0..=99)])
+/// ```
+///
+/// The run stops with an error where the program's control flow or the
+/// addresses it writes to would depend on symbols.
+pub struct Symbolic {
+    instruction_set: HashMap<i8, InstructionDef>,
+    memory: HashMap<u128, Expr>,
+    pc: u128,
+    relative_base: i128,
+    input: VecDeque<Expr>,
+    output: Vec<Expr>,
+}
+
+impl Symbolic {
+    /// Starts from the memory, pc, relative base and pending input of `automaton`.
+    pub fn new<W: FixedWord>(automaton: &Automaton<W>) -> Self {
+        Self {
+            instruction_set: automaton.instruction_set.clone(),
+            memory: automaton.memory.cells().into_iter()
+                .map(|(address, value)| (address, Expr::Const(value.into())))
+                .collect(),
+            pc: automaton.pc,
+            relative_base: automaton.relative_base,
+            input: automaton.input.iter().map(|&value| Expr::Const(value.into())).collect(),
+            output: Vec::new(),
+        }
+    }
+
+    /// Replaces the cell at `address` with the symbol `name`.
+    pub fn with_symbol(mut self, address: u128, name: &str) -> Self {
+        self.memory.insert(address, Expr::symbol(name));
+
+        self
+    }
+
+    pub fn add_input(mut self, value: i128) -> Self {
+        self.input.push_back(Expr::Const(value));
+
+        self
+    }
+
+    /// Queues the symbol `name` as the next input.
+    pub fn add_symbolic_input(mut self, name: &str) -> Self {
+        self.input.push_back(Expr::symbol(name));
+
+        self
+    }
+
+    /// Runs until the program halts or waits for input.
+    pub fn run(&mut self) -> Result<RunState, SymbolicError> {
+        loop {
+            let instruction = self.decode()?;
+            let params = instruction.params;
+            let next = self.pc + instruction.len();
+            match instruction.opcode {
+                1 | 2 | 7 | 8 => {
+                    let (a, b) = (self.read(params[0])?, self.read(params[1])?);
+                    let value = match (instruction.opcode, a.as_const(), b.as_const()) {
+                        (1, Some(a), Some(b)) => Expr::Const(a.checked_add(b).ok_or(IntcodeError::Overflow { pc: self.pc })?),
+                        (2, Some(a), Some(b)) => Expr::Const(a.checked_mul(b).ok_or(IntcodeError::Overflow { pc: self.pc })?),
+                        (1, ..) => a + b,
+                        (2, ..) => a * b,
+                        (7, ..) => a.less_than(b),
+                        _ => a.equals(b),
+                    };
+                    self.write(params[2], value)?;
+                }
+                3 => {
+                    let Some(value) = self.input.pop_front() else {
+                        return Ok(RunState::AwaitingInput);
+                    };
+                    self.write(params[0], value)?;
+                }
+                4 => {
+                    let value = self.read(params[0])?;
+                    self.output.push(value);
+                }
+                5 | 6 => {
+                    let condition = self.read(params[0])?;
+                    let Some(condition) = condition.as_const() else {
+                        return Err(SymbolicError::SymbolicBranch { pc: self.pc, condition });
+                    };
+                    if (condition != 0) == (instruction.opcode == 5) {
+                        let target = self.read(params[1])?;
+                        self.pc = self.concrete_address(target)?;
+                        continue;
+                    }
+                }
+                9 => {
+                    let offset = self.read(params[0])?;
+                    let offset = offset.as_const()
+                        .ok_or(SymbolicError::SymbolicAddress { pc: self.pc, address: offset })?;
+                    self.relative_base = self.relative_base.checked_add(offset)
+                        .ok_or(IntcodeError::RelativeBaseOverflow { pc: self.pc })?;
+                }
+                99 => return Ok(RunState::Halted),
+                opcode => return Err(IntcodeError::InvalidOpcode { pc: self.pc, opcode: opcode as i128 }.into()),
+            }
+            self.pc = next;
+        }
+    }
+
+    /// The expression in the cell at `address`.
+    pub fn value(&self, address: u128) -> Expr {
+        self.memory.get(&address).cloned().unwrap_or(Expr::Const(0))
+    }
+
+    pub fn outputs(&self) -> &[Expr] {
+        &self.output
+    }
+
+    fn decode(&self) -> Result<Instruction, SymbolicError> {
+        match self.value(self.pc) {
+            Expr::Const(value) => Ok(decode_value(&self.instruction_set, self.pc, value)?),
+            value => Err(SymbolicError::SymbolicInstruction { pc: self.pc, value }),
+        }
+    }
+
+    /// The address a parameter refers to, which is symbolic if the address
+    /// stored in the parameter is.
+    fn address(&self, param: Parameter) -> Expr {
+        match param.mode {
+            ParameterMode::Immediate => Expr::Const(param.address as i128),
+            ParameterMode::Position => self.value(param.address),
+            ParameterMode::Relative => self.value(param.address) + Expr::Const(self.relative_base),
+        }
+    }
+
+    fn concrete_address(&self, address: Expr) -> Result<u128, SymbolicError> {
+        match address {
+            Expr::Const(address) if address < 0 => Err(IntcodeError::NegativeAddress { pc: self.pc, address }.into()),
+            Expr::Const(address) => Ok(address as u128),
+            address => Err(SymbolicError::SymbolicAddress { pc: self.pc, address }),
+        }
+    }
+
+    fn read(&self, param: Parameter) -> Result<Expr, SymbolicError> {
+        match self.address(param) {
+            Expr::Const(address) => Ok(self.value(self.concrete_address(Expr::Const(address))?)),
+            address => Ok(Expr::Load(Box::new(address))),
+        }
+    }
+
+    fn write(&mut self, param: Parameter, value: Expr) -> Result<(), SymbolicError> {
+        if let ParameterMode::Immediate = param.mode {
+            return Err(IntcodeError::WriteToImmediate { pc: self.pc }.into());
+        }
+        let address = self.concrete_address(self.address(param))?;
+        self.memory.insert(address, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::intcode::{Automaton, RunState};
+    use crate::intcode::symbolic::{Expr, Symbolic, SymbolicError};
+
+    // day 2 style: the instruction at 0 reads from the noun and verb as
+    // addresses, the rest computes memory[0] = 5 * noun + verb + 3
+    const NOUN_VERB: &str = "1,0,0,3,1002,1,5,17,1,17,2,17,1001,17,3,0,99,0";
+
+    #[test]
+    fn test_solve_noun_and_verb() {
+        let mut symbolic = Symbolic::new(&Automaton::new_with_program(NOUN_VERB))
+            .with_symbol(1, "noun")
+            .with_symbol(2, "verb");
+        assert_eq!(symbolic.run(), Ok(RunState::Halted));
+        assert_eq!(symbolic.value(3).to_string(), "[noun] + [verb]");
+
+        let result = symbolic.value(0);
+        assert_eq!(result.to_string(), "5 * noun + verb + 3");
+        let linear = result.linear().unwrap();
+        assert_eq!(linear.solve(500, &[("noun", 0..=99), ("verb", 0..=99)]), Some(vec![80, 97]));
+        assert_eq!(linear.solve(600, &[("noun", 0..=99), ("verb", 0..=99)]), None);
+
+        let values = HashMap::from([(String::from("noun"), 80), (String::from("verb"), 97)]);
+        assert_eq!(result.eval(&values), Some(500));
+    }
+
+    #[test]
+    fn test_symbolic_input_and_output() {
+        // outputs (x * y < 10) and 2 * (x + 1)
+        let program = "3,30,3,31,2,30,31,32,1007,32,10,33,4,33,1001,30,1,30,102,2,30,30,4,30,99";
+        let mut symbolic = Symbolic::new(&Automaton::new_with_program(program)).add_symbolic_input("x");
+        assert_eq!(symbolic.run(), Ok(RunState::AwaitingInput));
+
+        let mut symbolic = symbolic.add_symbolic_input("y");
+        assert_eq!(symbolic.run(), Ok(RunState::Halted));
+        let outputs : Vec<String> = symbolic.outputs().iter().map(Expr::to_string).collect();
+        assert_eq!(outputs, vec!["(x * y < 10)", "2 * x + 2"]);
+    }
+
+    #[test]
+    fn test_symbolic_branch() {
+        let program = "3,10,1005,10,7,99,99,104,1,99";
+        let mut symbolic = Symbolic::new(&Automaton::new_with_program(program)).add_symbolic_input("x");
+        assert_eq!(symbolic.run(), Err(SymbolicError::SymbolicBranch { pc: 2, condition: Expr::symbol("x") }));
+
+        let mut symbolic = Symbolic::new(&Automaton::new_with_program(program)).add_input(1);
+        assert_eq!(symbolic.run(), Ok(RunState::Halted));
+        assert_eq!(symbolic.outputs(), &[Expr::Const(1)]);
+    }
+
+    #[test]
+    fn test_loads_are_not_compared() {
+        // reads [p] twice with a write in between, then compares the reads
+        let program = "1001,0,0,50,1101,7,0,60,1001,0,0,51,8,50,51,52,99";
+        let mut symbolic = Symbolic::new(&Automaton::new_with_program(program))
+            .with_symbol(1, "p")
+            .with_symbol(9, "p");
+        assert_eq!(symbolic.run(), Ok(RunState::Halted));
+        assert_eq!(symbolic.value(52).to_string(), "([p] + 0 == [p] + 0)");
+
+        let mut automaton = Automaton::new_with_program(program).with_patch(1, 60).with_patch(9, 60);
+        automaton.run().unwrap();
+        assert_eq!(automaton.peek(52), 0);
+    }
+
+    #[test]
+    fn test_overflowing_coefficients() {
+        // multiplies the input by 2^64 twice
+        let program = "3,20,1002,20,18446744073709551616,20,1002,20,18446744073709551616,20,99";
+        let mut symbolic = Symbolic::new(&Automaton::new_with_program(program)).add_symbolic_input("x");
+        assert_eq!(symbolic.run(), Ok(RunState::Halted));
+        assert_eq!(symbolic.value(20).to_string(), "18446744073709551616 * 18446744073709551616 * x");
+        assert_eq!(symbolic.value(20).linear(), None);
+    }
+}
@@ -0,0 +1,269 @@
+use std::io::{self, Read, Write};
+use crate::intcode::{Automaton, Instruction, IntcodeError, ParameterMode, RunState};
+use crate::intcode::word::Word;
+use crate::intcode::varint::{read_signed, read_unsigned, write_signed, write_unsigned};
+
+/// Everything one executed instruction observed and changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub pc: u128,
+    pub opcode: i8,
+    /// Resolved values of the parameters the instruction reads.
+    pub operands: Vec<i128>,
+    /// `(address, value)` pairs written to memory.
+    pub writes: Vec<(u128, i128)>,
+    /// The new relative base, if the instruction changed it.
+    pub relative_base: Option<i128>,
+    pub input: Option<i128>,
+    pub output: Option<i128>,
+}
+
+/// The recorded instructions of a traced `Automaton`, in execution order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+}
+
+const MAGIC: &[u8; 4] = b"ICTR";
+const VERSION: u8 = 1;
+
+const HAS_RELATIVE_BASE: u8 = 1;
+const HAS_INPUT: u8 = 2;
+const HAS_OUTPUT: u8 = 4;
+
+impl Trace {
+    /// Index of the first event that differs between two traces, or of the
+    /// first event only one of them has.
+    pub fn first_divergence(&self, other: &Trace) -> Option<usize> {
+        let common = self.events.iter().zip(&other.events)
+            .position(|(left, right)| left != right);
+        match common {
+            Some(step) => Some(step),
+            None if self.events.len() != other.events.len() => Some(self.events.len().min(other.events.len())),
+            None => None,
+        }
+    }
+
+    /// One JSON object per line, numbered by `step`.
+    pub fn write_json_lines(&self, out: &mut impl Write) -> io::Result<()> {
+        fn optional(value: Option<i128>) -> String {
+            value.map_or(String::from("null"), |value| value.to_string())
+        }
+
+        for (step, event) in self.events.iter().enumerate() {
+            let operands = event.operands.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(",");
+            let writes = event.writes.iter().map(|(address, value)| format!("[{address},{value}]")).collect::<Vec<_>>().join(",");
+            writeln!(out, r#"{{"step":{step},"pc":{},"opcode":{},"operands":[{operands}],"writes":[{writes}],"relative_base":{},"input":{},"output":{}}}"#,
+                event.pc, event.opcode, optional(event.relative_base), optional(event.input), optional(event.output))?;
+        }
+        Ok(())
+    }
+
+    /// A header of `ICTR`, a version byte and the event count, followed by the
+    /// events with every number stored as a LEB128 varint (zigzag for signed).
+    pub fn write_binary(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        write_unsigned(out, self.events.len() as u128)?;
+
+        for event in &self.events {
+            let flags = [(event.relative_base, HAS_RELATIVE_BASE), (event.input, HAS_INPUT), (event.output, HAS_OUTPUT)].iter()
+                .filter(|(value, _)| value.is_some())
+                .fold(0, |flags, (_, flag)| flags | flag);
+
+            write_unsigned(out, event.pc)?;
+            out.write_all(&[event.opcode as u8, flags])?;
+            write_unsigned(out, event.operands.len() as u128)?;
+            for &operand in &event.operands {
+                write_signed(out, operand)?;
+            }
+            write_unsigned(out, event.writes.len() as u128)?;
+            for &(address, value) in &event.writes {
+                write_unsigned(out, address)?;
+                write_signed(out, value)?;
+            }
+            for value in [event.relative_base, event.input, event.output].into_iter().flatten() {
+                write_signed(out, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_binary(input: &mut impl Read) -> io::Result<Trace> {
+        let mut header = [0; 5];
+        input.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an Intcode trace of a supported version"));
+        }
+
+        let count = read_unsigned(input)?;
+        let mut events = Vec::new();
+        for _ in 0..count {
+            let pc = read_unsigned(input)?;
+            let mut opcode_and_flags = [0; 2];
+            input.read_exact(&mut opcode_and_flags)?;
+            let [opcode, flags] = opcode_and_flags;
+
+            let operands = (0..read_unsigned(input)?)
+                .map(|_| read_signed(input))
+                .collect::<io::Result<_>>()?;
+            let writes = (0..read_unsigned(input)?)
+                .map(|_| Ok((read_unsigned(input)?, read_signed(input)?)))
+                .collect::<io::Result<_>>()?;
+            let mut optional = |flag: u8| -> io::Result<Option<i128>> {
+                if flags & flag != 0 { read_signed(input).map(Some) } else { Ok(None) }
+            };
+
+            events.push(TraceEvent {
+                pc,
+                opcode: opcode as i8,
+                operands,
+                writes,
+                relative_base: optional(HAS_RELATIVE_BASE)?,
+                input: optional(HAS_INPUT)?,
+                output: optional(HAS_OUTPUT)?,
+            });
+        }
+        Ok(Trace { events })
+    }
+}
+
+/// What `trace_after` needs to know about the machine before the instruction ran.
+pub(crate) struct PendingEvent {
+    event: TraceEvent,
+    destination: Option<u128>,
+    relative_base: i128,
+}
+
+impl<W: Word> Automaton<W> {
+    /// Records every executed instruction from now on; see `trace`.
+    pub fn with_tracing(mut self) -> Self {
+        self.trace = Some(Trace::default());
+
+        self
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Returns the trace recorded so far and stops tracing.
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    pub(crate) fn trace_before(&self, instr: &Instruction) -> Result<PendingEvent, IntcodeError> {
+        let writes = self.instruction_set.get(&instr.opcode).is_some_and(|def| def.writes);
+        let params = &instr.params[..instr.no_params];
+        let (reads, destination) = match (writes, params.split_last()) {
+            (true, Some((dest, reads))) => (reads, Some(dest)),
+            _ => (params, None),
+        };
+
+        let operands = reads.iter()
+            .map(|param| self.read_value(param.address, param.mode).and_then(|value| self.to_i128(&value)))
+            .collect::<Result<_, _>>()?;
+        let destination = match destination {
+            Some(dest) if !matches!(dest.mode, ParameterMode::Immediate) => Some(self.parameter_to_address(dest.address, dest.mode)?),
+            _ => None,
+        };
+
+        Ok(PendingEvent {
+            event: TraceEvent { pc: self.pc, opcode: instr.opcode, operands, ..TraceEvent::default() },
+            destination,
+            relative_base: self.relative_base,
+        })
+    }
+
+    pub(crate) fn trace_after(&mut self, pending: PendingEvent) -> Result<(), IntcodeError> {
+        if matches!(self.state, RunState::AwaitingInput | RunState::Overflowed) {
+            return Ok(());
+        }
+        let PendingEvent { mut event, destination, relative_base } = pending;
+
+        event.writes = match destination {
+            Some(address) => vec![(address, self.to_i128(&self.memory.read(address))?)],
+            None => Vec::new(),
+        };
+        event.relative_base = (self.relative_base != relative_base).then_some(self.relative_base);
+        // the value read is the one written, the value written out the only operand
+        event.input = if event.opcode == 3 { event.writes.first().map(|&(_, value)| value) } else { None };
+        event.output = if event.opcode == 4 { event.operands.first().copied() } else { None };
+
+        if let Some(trace) = self.trace.as_mut() {
+            trace.events.push(event);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::Automaton;
+    use crate::intcode::trace::{Trace, TraceEvent};
+
+    fn traced(program: &str, input: i128) -> Trace {
+        let mut automaton = Automaton::new_with_program(program)
+            .add_initial_input(input)
+            .with_tracing();
+        automaton.run().unwrap();
+        automaton.take_trace().unwrap()
+    }
+
+    #[test]
+    fn test_trace_events() {
+        let trace = traced("3,11,109,4,1001,11,-5,11,204,7,99,0", 8);
+        assert_eq!(trace.events, vec![
+            TraceEvent { pc: 0, opcode: 3, writes: vec![(11, 8)], input: Some(8), ..TraceEvent::default() },
+            TraceEvent { pc: 2, opcode: 9, operands: vec![4], relative_base: Some(4), ..TraceEvent::default() },
+            TraceEvent { pc: 4, opcode: 1, operands: vec![8, -5], writes: vec![(11, 3)], ..TraceEvent::default() },
+            TraceEvent { pc: 8, opcode: 4, operands: vec![3], output: Some(3), ..TraceEvent::default() },
+            TraceEvent { pc: 10, opcode: 99, ..TraceEvent::default() },
+        ]);
+    }
+
+    #[test]
+    fn test_blocked_input_is_not_recorded() {
+        let mut automaton = Automaton::new_with_program("3,5,4,5,99,0").with_tracing();
+        automaton.run().unwrap();
+        assert!(automaton.trace().unwrap().events.is_empty());
+
+        automaton.runtime_input(6).unwrap();
+        automaton.run().unwrap();
+        assert_eq!(automaton.trace().unwrap().events.len(), 3);
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let mut out = Vec::new();
+        traced("3,11,109,4,1001,11,-5,11,204,7,99,0", 8).write_json_lines(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines : Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], r#"{"step":0,"pc":0,"opcode":3,"operands":[],"writes":[[11,8]],"relative_base":null,"input":8,"output":null}"#);
+        assert_eq!(lines[2], r#"{"step":2,"pc":4,"opcode":1,"operands":[8,-5],"writes":[[11,3]],"relative_base":null,"input":null,"output":null}"#);
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let program = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99,104,1125899906842624,99";
+        let trace = traced(program, 0);
+        let mut out = Vec::new();
+        trace.write_binary(&mut out).unwrap();
+        assert_eq!(Trace::read_binary(&mut out.as_slice()).unwrap(), trace);
+        assert!(Trace::read_binary(&mut &b"JUNK"[..]).is_err());
+    }
+
+    #[test]
+    fn test_first_divergence() {
+        let program = "3,3,1105,-1,9,1101,0,0,12,4,12,99,1";
+        let zero = traced(program, 0);
+        let one = traced(program, 1);
+        assert_eq!(zero.first_divergence(&zero), None);
+        assert_eq!(zero.first_divergence(&one), Some(0));
+
+        let mut shorter = zero.clone();
+        shorter.events.pop();
+        assert_eq!(zero.first_divergence(&shorter), Some(shorter.events.len()));
+    }
+}
@@ -0,0 +1,37 @@
+// LEB128 varints used by the binary trace and snapshot formats. Signed
+// values are zigzag-encoded so small negative numbers stay short.
+
+use std::io::{self, Read, Write};
+
+pub(crate) fn write_unsigned(out: &mut impl Write, mut value: u128) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+pub(crate) fn write_signed(out: &mut impl Write, value: i128) -> io::Result<()> {
+    write_unsigned(out, ((value << 1) ^ (value >> 127)) as u128)
+}
+
+pub(crate) fn read_unsigned(input: &mut impl Read) -> io::Result<u128> {
+    let mut value = 0;
+    for shift in (0..128).step_by(7) {
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u128) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "varint too long"))
+}
+
+pub(crate) fn read_signed(input: &mut impl Read) -> io::Result<i128> {
+    let value = read_unsigned(input)?;
+    Ok((value >> 1) as i128 ^ -((value & 1) as i128))
+}
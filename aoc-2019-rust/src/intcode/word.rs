@@ -0,0 +1,116 @@
+use std::fmt;
+use std::str::FromStr;
+pub use num_bigint::BigInt;
+
+/// The integer type of a memory cell, input and output of an `Automaton`.
+/// `i128` is the default; `i64` needs half the memory but overflows sooner,
+/// and `BigInt` never overflows but allocates for every value.
+///
+/// Addresses, opcodes and the values handed to an `OutputSink` or a trace
+/// are `i128`; a word that does not fit one there faults the machine.
+pub trait Word: Clone + Default + Ord + fmt::Debug + fmt::Display + FromStr + From<u8> + TryFrom<i128> + Send + Sync + 'static {
+    const ZERO: Self;
+
+    /// The value as an `i128`, `None` if it does not fit.
+    fn to_i128(&self) -> Option<i128>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+    fn wrapping_add(&self, other: &Self) -> Self;
+    fn wrapping_mul(&self, other: &Self) -> Self;
+}
+
+/// A fixed-width word that always fits an `i128`. The helpers that hand out
+/// `i128` values, such as frames, ASCII text, disassembly and snapshots,
+/// work on these, and `Automaton::promote` starts from one.
+pub trait FixedWord: Word + Copy + Into<i128> {}
+
+impl<W: Word + Copy + Into<i128>> FixedWord for W {}
+
+impl Word for i64 {
+    const ZERO: Self = 0;
+
+    fn to_i128(&self) -> Option<i128> {
+        Some(i128::from(*self))
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        i64::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        i64::checked_mul(*self, *other)
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        i64::wrapping_add(*self, *other)
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        i64::wrapping_mul(*self, *other)
+    }
+}
+
+impl Word for i128 {
+    const ZERO: Self = 0;
+
+    fn to_i128(&self) -> Option<i128> {
+        Some(*self)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        i128::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        i128::checked_mul(*self, *other)
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        i128::wrapping_add(*self, *other)
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        i128::wrapping_mul(*self, *other)
+    }
+}
+
+/// Arbitrary precision: `ADD` and `MUL` never overflow, so the overflow
+/// policy does not apply.
+impl Word for BigInt {
+    const ZERO: Self = BigInt::ZERO;
+
+    fn to_i128(&self) -> Option<i128> {
+        i128::try_from(self).ok()
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        self * other
+    }
+}
+
+/// What an `Automaton` does when `ADD` or `MUL` overflows its word type.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Overflow {
+    /// Two's complement wrap-around, like the machine words of real hardware.
+    Wrap,
+    /// Fault with `IntcodeError::Overflow`.
+    #[default]
+    Fault,
+    /// Stop in `RunState::Overflowed` before the instruction has any effect,
+    /// so that `Automaton::promote` can continue the program on `BigInt`
+    /// words.
+    Promote,
+}